@@ -0,0 +1,540 @@
+use std::time::Instant;
+
+use crate::{
+    grid::Grid,
+    settings::{Difficulty, Variant},
+    Board, State, TileState,
+};
+
+/// One bit per tile, kept row by row in 64 bit words. Every row starts on a new word, and the
+/// bits past the right edge of the board are always clear.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Bitboard {
+    width: usize,
+    height: usize,
+    /// Words per row.
+    stride: usize,
+    words: Vec<u64>,
+}
+
+impl Bitboard {
+    pub fn new(width: usize, height: usize) -> Self {
+        let stride = width.div_ceil(64);
+
+        Self {
+            width,
+            height,
+            stride,
+            words: vec![0; stride * height],
+        }
+    }
+
+    pub fn from_grid(grid: &Grid<bool>) -> Self {
+        let mut bitboard = Self::new(grid.width(), grid.height());
+
+        for (col, row) in grid.positions() {
+            if grid[(col, row)] {
+                bitboard.set(col, row);
+            }
+        }
+
+        bitboard
+    }
+
+    pub fn to_grid(&self) -> Grid<bool> {
+        let mut grid = Grid::new(self.width, self.height, false);
+
+        for tile in self.positions() {
+            grid[tile] = true;
+        }
+
+        grid
+    }
+
+    pub fn set(&mut self, col: usize, row: usize) {
+        self.words[row * self.stride + col / 64] |= 1 << (col % 64);
+    }
+
+    pub fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    /// The `(col, row)` of every set bit, row by row.
+    pub fn positions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .flat_map(move |(index, &word)| {
+                let (row, word_col) = (index / self.stride, index % self.stride * 64);
+
+                std::iter::successors((word != 0).then_some(word), |&rest| {
+                    let rest = rest & (rest - 1);
+                    (rest != 0).then_some(rest)
+                })
+                .map(move |rest| (word_col + rest.trailing_zeros() as usize, row))
+            })
+    }
+
+    pub fn and(&self, other: &Self) -> Self {
+        self.zip(other, |a, b| a & b)
+    }
+
+    pub fn or(&self, other: &Self) -> Self {
+        self.zip(other, |a, b| a | b)
+    }
+
+    pub fn and_not(&self, other: &Self) -> Self {
+        self.zip(other, |a, b| a & !b)
+    }
+
+    pub fn not(&self) -> Self {
+        let mut result = self.clone();
+
+        for (index, word) in result.words.iter_mut().enumerate() {
+            *word = !*word & self.valid_bits(index % self.stride);
+        }
+
+        result
+    }
+
+    fn zip(&self, other: &Self, f: impl Fn(u64, u64) -> u64) -> Self {
+        debug_assert_eq!((self.width, self.height), (other.width, other.height));
+
+        Self {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(&a, &b)| f(a, b))
+                .collect(),
+            ..*self
+        }
+    }
+
+    /// Bits of the given word of a row that are on the board.
+    fn valid_bits(&self, word: usize) -> u64 {
+        let bits = self.width - word * 64;
+
+        if bits >= 64 {
+            u64::MAX
+        } else {
+            (1 << bits) - 1
+        }
+    }
+
+    /// A word of a row with every bit moved one column right, pulling in the top bit of the
+    /// word before it.
+    fn east(&self, row: &[u64], word: usize) -> u64 {
+        let carry = word
+            .checked_sub(1)
+            .map_or(0, |previous| row[previous] >> 63);
+        (row[word] << 1 | carry) & self.valid_bits(word)
+    }
+
+    /// A word of a row with every bit moved one column left, pulling in the bottom bit of the
+    /// word after it.
+    fn west(&self, row: &[u64], word: usize) -> u64 {
+        row[word] >> 1 | row.get(word + 1).map_or(0, |next| next << 63)
+    }
+
+    /// Applies `f` to the left, centre and right neighbours of every word.
+    fn horizontal(&self, mut f: impl FnMut(usize, u64, u64, u64)) {
+        for (row, words) in self.words.chunks(self.stride).enumerate() {
+            for word in 0..self.stride {
+                f(
+                    row * self.stride + word,
+                    self.east(words, word),
+                    words[word],
+                    self.west(words, word),
+                );
+            }
+        }
+    }
+
+    /// Every tile that is set or next to one that is.
+    pub fn neighbourhood(&self) -> Self {
+        let mut across = Self::new(self.width, self.height);
+        self.horizontal(|index, east, centre, west| across.words[index] = east | centre | west);
+
+        let mut result = across.clone();
+        for index in 0..self.words.len() {
+            if index >= self.stride {
+                result.words[index] |= across.words[index - self.stride];
+            }
+
+            if let Some(&below) = across.words.get(index + self.stride) {
+                result.words[index] |= below;
+            }
+        }
+
+        result
+    }
+
+    /// How many set tiles each tile has around it, added up a word at a time: first along each
+    /// row, then the sums of the rows above and below are added to the sides of the row itself.
+    pub fn neighbour_counts(&self) -> Counts {
+        // two bit sums of the three tiles in a row, and of just the two either side
+        let mut across = vec![[0; 2]; self.words.len()];
+        let mut sides = vec![[0; 2]; self.words.len()];
+
+        self.horizontal(|index, east, centre, west| {
+            across[index] = [east ^ centre ^ west, east & west | centre & (east ^ west)];
+            sides[index] = [east ^ west, east & west];
+        });
+
+        let words = (0..self.words.len())
+            .map(|index| {
+                let mut count = [0; 4];
+                add(&mut count, sides[index]);
+
+                if let Some(above) = index.checked_sub(self.stride) {
+                    add(&mut count, across[above]);
+                }
+
+                if let Some(&below) = across.get(index + self.stride) {
+                    add(&mut count, below);
+                }
+
+                count
+            })
+            .collect();
+
+        Counts {
+            layout: Self::new(self.width, self.height),
+            words,
+        }
+    }
+}
+
+/// Ripple carry adds a two bit number into a four bit one, for 64 tiles at once.
+fn add(count: &mut [u64; 4], addend: [u64; 2]) {
+    let mut carry = 0;
+
+    for (bit, plane) in count.iter_mut().enumerate() {
+        let addend = addend.get(bit).copied().unwrap_or(0);
+        let sum = *plane ^ addend ^ carry;
+        carry = *plane & addend | carry & (*plane ^ addend);
+        *plane = sum;
+    }
+}
+
+/// A count from 0 to 8 for every tile, bit sliced so that bit `i` of the counts of the tiles
+/// in a word of a bitboard lives in the `i`th entry of the matching word here.
+pub struct Counts {
+    /// An empty bitboard of the same size, to build results from.
+    layout: Bitboard,
+    words: Vec<[u64; 4]>,
+}
+
+impl Counts {
+    /// Tiles where both counts are the same.
+    pub fn equal(&self, other: &Counts) -> Bitboard {
+        self.select(|index, count| {
+            let different = count
+                .iter()
+                .zip(&other.words[index])
+                .fold(0, |different, (a, b)| different | (a ^ b));
+            !different
+        })
+    }
+
+    /// Tiles with a count of zero.
+    pub fn zero(&self) -> Bitboard {
+        self.select(|_, count| !count.iter().fold(0, |any, plane| any | plane))
+    }
+
+    /// A bitboard from a word of tiles worked out from each word of counts.
+    fn select(&self, f: impl Fn(usize, &[u64; 4]) -> u64) -> Bitboard {
+        let mut result = self.layout.clone();
+
+        for (index, count) in self.words.iter().enumerate() {
+            result.words[index] = f(index, count) & result.valid_bits(index % result.stride);
+        }
+
+        result
+    }
+}
+
+/// A game kept entirely in bitboards, for simulating and solving many games quickly. The
+/// shifts stop at the edges and a bit holds one mine, so only the classic flat board with a
+/// mine to a tile can be played this way.
+pub struct BitGame {
+    pub mines: Bitboard,
+    pub revealed: Bitboard,
+    pub flagged: Bitboard,
+    counts: Counts,
+    /// Safe tiles with no mines around them, which open up their neighbours when revealed.
+    zeros: Bitboard,
+}
+
+impl BitGame {
+    pub fn new(mines: Bitboard) -> Self {
+        let counts = mines.neighbour_counts();
+        let zeros = counts.zero();
+
+        Self {
+            revealed: Bitboard::new(mines.width, mines.height),
+            flagged: Bitboard::new(mines.width, mines.height),
+            zeros: zeros.and_not(&mines),
+            counts,
+            mines,
+        }
+    }
+
+    /// Places mines the same way as the board does for the same random numbers, then makes
+    /// the first click.
+    pub fn generate(
+        difficulty: Difficulty,
+        start_col: usize,
+        start_row: usize,
+        rng: &mut fastrand::Rng,
+    ) -> Self {
+        let (width, height) = (difficulty.width(), difficulty.height());
+        let mut candidates = (0..height)
+            .flat_map(|row| (0..width).map(move |col| (col, row)))
+            .filter(|&(col, row)| col.abs_diff(start_col) > 1 || row.abs_diff(start_row) > 1)
+            .collect::<Vec<_>>();
+
+        let mut mines = Bitboard::new(width, height);
+        for i in 0..difficulty.mines().min(candidates.len()) {
+            let j = rng.usize(i..candidates.len());
+            candidates.swap(i, j);
+            mines.set(candidates[i].0, candidates[i].1);
+        }
+
+        let mut game = Self::new(mines);
+        let mut start = Bitboard::new(width, height);
+        start.set(start_col, start_row);
+        game.reveal(&start);
+
+        game
+    }
+
+    pub fn from_board(board: &Board) -> Self {
+        let layer =
+            |f: fn(TileState) -> bool| Bitboard::from_grid(&board.tiles.map(|tile| f(tile.state)));
+
+        Self {
+            revealed: layer(|state| state == TileState::Revealed),
+            flagged: layer(|state| state == TileState::Flagged),
+            ..Self::new(Bitboard::from_grid(
+                &board.mine_layout().map(|&mines| mines > 0),
+            ))
+        }
+    }
+
+    /// The game as a board, with the same tiles open and flagged.
+    pub fn to_board(&self) -> Board {
+        let mut board = Board::with_layout(
+            &self.mines.to_grid().map(|&mine| mine as u8),
+            Variant::default(),
+        );
+
+        for tile in self.flagged.positions() {
+            board.tiles[tile].state = TileState::Flagged;
+        }
+
+        for tile in self.revealed.positions() {
+            board.tiles[tile].state = TileState::Revealed;
+        }
+
+        board.number_flagged = self.flagged.count();
+        board.revealed_safe = self.revealed.and_not(&self.mines).count();
+        board.state = if self.revealed.and(&self.mines).is_empty() {
+            if self.won() {
+                State::Won
+            } else {
+                State::Playing
+            }
+        } else {
+            State::Dead
+        };
+
+        board
+    }
+
+    /// Reveals every tile in the mask, opening up the empty space around any zeros.
+    pub fn reveal(&mut self, mask: &Bitboard) {
+        self.revealed = self.revealed.or(mask);
+        let mut frontier = mask.and(&self.zeros);
+
+        while !frontier.is_empty() {
+            let opened = frontier.neighbourhood().and_not(&self.revealed);
+            self.revealed = self.revealed.or(&opened);
+            frontier = opened.and(&self.zeros);
+        }
+    }
+
+    pub fn won(&self) -> bool {
+        self.revealed.count() == self.mines.width * self.mines.height - self.mines.count()
+    }
+
+    /// Flags around numbers with as many hidden tiles as mines, and clears around numbers with
+    /// as many flags as mines, until neither finds anything. Returns whether that won the game.
+    pub fn solve(&mut self) -> bool {
+        loop {
+            let hidden = self.revealed.not();
+            let numbers = self.revealed.and_not(&self.mines);
+
+            let all_mines = numbers.and(&self.counts.equal(&hidden.neighbour_counts()));
+            let to_flag = all_mines
+                .neighbourhood()
+                .and(&hidden)
+                .and_not(&self.flagged);
+
+            let satisfied = numbers.and(&self.counts.equal(&self.flagged.neighbour_counts()));
+            let to_reveal = satisfied
+                .neighbourhood()
+                .and(&hidden)
+                .and_not(&self.flagged)
+                .and_not(&to_flag);
+
+            if to_flag.is_empty() && to_reveal.is_empty() {
+                return self.won();
+            }
+
+            self.flagged = self.flagged.or(&to_flag);
+            self.reveal(&to_reveal);
+        }
+    }
+}
+
+/// Generates and solves a board for every seed from `first_seed` on, starting in the middle,
+/// and prints how long that took and how many the solver won.
+pub fn bench(difficulty: Difficulty, first_seed: u64, boards: u64) {
+    let (start_col, start_row) = (difficulty.width() / 2, difficulty.height() / 2);
+    let timer = Instant::now();
+    let mut won = 0;
+
+    for board in 0..boards {
+        let seed = first_seed.wrapping_add(board);
+        let mut game = BitGame::generate(
+            difficulty,
+            start_col,
+            start_row,
+            &mut fastrand::Rng::with_seed(seed),
+        );
+
+        if game.solve() {
+            won += 1;
+        }
+    }
+
+    let seconds = timer.elapsed().as_secs_f64();
+    println!(
+        "{boards} {} boards in {seconds:.2}s ({:.0} a second), solver won {won} ({:.1}%)",
+        difficulty.name(),
+        boards as f64 / seconds,
+        100.0 * won as f64 / boards.max(1) as f64
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_fair_game, settings::Variant};
+
+    const SEEDS: u64 = 100;
+
+    /// The count for one tile, put back together from its bit slices.
+    fn count_at(counts: &Counts, bitboard: &Bitboard, col: usize, row: usize) -> u8 {
+        let (index, bit) = (row * bitboard.stride + col / 64, col % 64);
+
+        counts.words[index]
+            .iter()
+            .enumerate()
+            .map(|(plane, word)| ((word >> bit & 1) as u8) << plane)
+            .sum()
+    }
+
+    #[test]
+    fn neighbour_counts_match_the_board() {
+        // wide enough for rows to take two words, so the carries between words are covered
+        for (width, height) in [(9, 9), (30, 16), (70, 5)] {
+            for seed in 0..SEEDS {
+                let tiles = generate_fair_game(
+                    0,
+                    0,
+                    width,
+                    height,
+                    width * height / 4,
+                    Variant::default(),
+                    &mut fastrand::Rng::with_seed(seed),
+                );
+                let mines = Bitboard::from_grid(&tiles.map(|tile| tile.mines > 0));
+                let counts = mines.neighbour_counts();
+
+                for (col, row) in tiles.positions() {
+                    assert_eq!(
+                        count_at(&counts, &mines, col, row),
+                        tiles[(col, row)].neighbour_mines_count,
+                        "seed {seed} on {width}x{height} at {col} {row}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generate_matches_the_board() {
+        let difficulty = Difficulty::Expert;
+        let (start_col, start_row) = (difficulty.width() / 2, difficulty.height() / 2);
+
+        for seed in 0..SEEDS {
+            let game = BitGame::generate(
+                difficulty,
+                start_col,
+                start_row,
+                &mut fastrand::Rng::with_seed(seed),
+            );
+            let tiles = generate_fair_game(
+                start_col,
+                start_row,
+                difficulty.width(),
+                difficulty.height(),
+                difficulty.mines(),
+                Variant::default(),
+                &mut fastrand::Rng::with_seed(seed),
+            );
+
+            assert!(
+                tiles.map(|tile| tile.mines > 0) == game.mines.to_grid(),
+                "seed {seed} places different mines"
+            );
+
+            let mut board = Board::with_layout(&tiles.map(|tile| tile.mines), Variant::default());
+            board.start(start_col, start_row);
+
+            assert!(
+                BitGame::from_board(&board).revealed == game.revealed,
+                "seed {seed} opens up different space"
+            );
+        }
+    }
+
+    #[test]
+    fn solved_games_survive_conversion() {
+        let difficulty = Difficulty::Intermediate;
+
+        for seed in 0..SEEDS {
+            let mut game = BitGame::generate(difficulty, 8, 8, &mut fastrand::Rng::with_seed(seed));
+            game.solve();
+
+            let board = game.to_board();
+            let back = BitGame::from_board(&board);
+
+            assert_eq!(board.is_game_won(), game.won(), "seed {seed}");
+            assert!(
+                back.revealed == game.revealed && back.flagged == game.flagged,
+                "seed {seed} changes when converted to a board and back"
+            );
+        }
+    }
+}
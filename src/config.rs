@@ -0,0 +1,572 @@
+use std::{fs, path::PathBuf, time::Duration};
+
+use clap::{ArgGroup, Parser};
+use eyre::{ensure, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    grid::{Clues, Topology},
+    palette::{NumberStyle, Palette},
+    settings::{
+        ChordStyle, Difficulty, Fog, Mode, Settings, Solver, TimerPrecision, Variant, WrongFlags,
+        MAX_CUSTOM_HEIGHT, MAX_CUSTOM_WIDTH, MAX_MINES_PER_TILE, MIN_CUSTOM_SIZE,
+    },
+    theme,
+};
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// The directory holding the config file and any themes the user has installed.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("minesweeper"))
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum DifficultyName {
+    Beginner,
+    Intermediate,
+    Expert,
+    Custom,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CustomSize {
+    width: usize,
+    height: usize,
+    mines: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct Controls {
+    question_marks: bool,
+    chord_style: ChordStyle,
+    wrong_flags: WrongFlags,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        let settings = Settings::default();
+
+        Self {
+            question_marks: settings.question_marks,
+            chord_style: settings.chord_style,
+            wrong_flags: settings.wrong_flags,
+        }
+    }
+}
+
+/// Defaults kept in `config.toml` in the user's config directory. The settings menu writes it
+/// back, and anything missing from it is taken from [`Settings::default`].
+#[derive(Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    difficulty: DifficultyName,
+    custom: CustomSize,
+    /// Name of the theme as given in its manifest.
+    pub theme: String,
+    scale: f32,
+    mode: Mode,
+    topology: Topology,
+    mines_per_tile: u8,
+    clues: Clues,
+    fog: Fog,
+    solver: Solver,
+    palette: Palette,
+    number_style: NumberStyle,
+    ghost_edges: bool,
+    timer_precision: TimerPrecision,
+    controls: Controls,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new(&Settings::default(), theme::CLASSIC)
+    }
+}
+
+impl Config {
+    pub fn new(settings: &Settings, theme: &str) -> Self {
+        let difficulty = match settings.difficulty {
+            Difficulty::Beginner => DifficultyName::Beginner,
+            Difficulty::Intermediate => DifficultyName::Intermediate,
+            Difficulty::Expert => DifficultyName::Expert,
+            Difficulty::Custom { .. } => DifficultyName::Custom,
+        };
+
+        Self {
+            difficulty,
+            custom: CustomSize {
+                width: settings.custom.width(),
+                height: settings.custom.height(),
+                mines: settings.custom.mines(),
+            },
+            theme: theme.to_owned(),
+            scale: settings.scale,
+            mode: settings.mode,
+            topology: settings.variant.topology,
+            mines_per_tile: settings.variant.mines_per_tile,
+            clues: settings.variant.clues,
+            fog: settings.fog,
+            solver: settings.solver,
+            palette: settings.palette,
+            number_style: settings.number_style,
+            ghost_edges: settings.ghost_edges,
+            timer_precision: settings.timer_precision,
+            controls: Controls {
+                question_marks: settings.question_marks,
+                chord_style: settings.chord_style,
+                wrong_flags: settings.wrong_flags,
+            },
+        }
+    }
+
+    pub fn settings(&self) -> Settings {
+        let variant = Variant {
+            topology: self.topology,
+            mines_per_tile: self.mines_per_tile.clamp(1, MAX_MINES_PER_TILE),
+            clues: self.clues,
+        };
+        let custom = Difficulty::custom(
+            self.custom.width,
+            self.custom.height,
+            self.custom.mines,
+            variant,
+        );
+        let difficulty = match self.difficulty {
+            DifficultyName::Beginner => Difficulty::Beginner,
+            DifficultyName::Intermediate => Difficulty::Intermediate,
+            DifficultyName::Expert => Difficulty::Expert,
+            DifficultyName::Custom => custom,
+        };
+
+        Settings {
+            difficulty,
+            custom,
+            mode: self.mode,
+            variant,
+            fog: self.fog,
+            solver: self.solver,
+            scale: if self.scale > 0.0 {
+                self.scale
+            } else {
+                Settings::default().scale
+            },
+            palette: self.palette,
+            number_style: self.number_style,
+            ghost_edges: self.ghost_edges,
+            question_marks: self.controls.question_marks,
+            chord_style: self.controls.chord_style,
+            wrong_flags: self.controls.wrong_flags,
+            timer_precision: self.timer_precision,
+        }
+    }
+
+    pub fn load() -> Self {
+        let Some(path) = config_path() else {
+            return Self::default();
+        };
+
+        fs::read_to_string(&path)
+            .ok()
+            .and_then(|config| match toml::from_str(&config) {
+                Ok(config) => Some(config),
+                Err(error) => {
+                    eprintln!("ignoring unreadable {}: {error}", path.display());
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = config_path() else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).wrap_err_with(|| format!("creating {}", dir.display()))?;
+        }
+
+        fs::write(&path, toml::to_string(self)?)
+            .wrap_err_with(|| format!("writing {}", path.display()))
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(CONFIG_FILE))
+}
+
+/// Who plays the second board in a race.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Racer {
+    Bot,
+    Keyboard,
+}
+
+/// Flags override the config file for this run only.
+#[derive(Parser)]
+#[command(version, about = "Minesweeper")]
+// flags that set up the same part of a game, so that others can conflict with all of them
+#[command(group(ArgGroup::new("size").multiple(true)))]
+#[command(group(ArgGroup::new("variant").multiple(true)))]
+#[command(group(ArgGroup::new("headless").multiple(true)))]
+#[command(group(ArgGroup::new("network").multiple(true)))]
+#[command(group(ArgGroup::new("practice").conflicts_with_all([
+    "size", "variant", "headless", "network", "daily", "race", "replay", "board", "bench",
+])))]
+pub struct Args {
+    /// Width of a custom board
+    #[arg(long, group = "size")]
+    pub width: Option<usize>,
+
+    /// Height of a custom board
+    #[arg(long, group = "size")]
+    pub height: Option<usize>,
+
+    /// Number of mines on a custom board
+    #[arg(long, group = "size")]
+    pub mines: Option<usize>,
+
+    /// Seed for the first board, so that the same first click gives the same mines
+    #[arg(long, group = "size")]
+    pub seed: Option<u64>,
+
+    /// Let the bot play
+    #[arg(long)]
+    pub bot: bool,
+
+    /// Wrap the board round at its edges, so that every tile has eight neighbours
+    #[arg(long, group = "variant")]
+    pub torus: bool,
+
+    /// Play on hexagonal tiles with six neighbours each
+    #[arg(long, group = "variant", conflicts_with = "torus")]
+    pub hex: bool,
+
+    /// Play on triangular tiles with twelve neighbours each
+    #[arg(long, group = "variant", conflicts_with_all = ["torus", "hex"])]
+    pub triangle: bool,
+
+    /// Let each tile hold up to this many mines, each needing its own flag
+    #[arg(long, value_name = "MINES", group = "variant")]
+    pub mines_per_tile: Option<u8>,
+
+    /// What the numbers count: the tiles around, a knight's move away, a cross two tiles out,
+    /// or the tiles around but always off by one
+    #[arg(long, value_name = "CLUES", group = "variant")]
+    pub clues: Option<Clues>,
+
+    /// Only show the numbers near the last few reveals, or near the mouse
+    #[arg(long, value_name = "FOG")]
+    pub fog: Option<Fog>,
+
+    /// Play today's daily board, the same expert board for everyone that needs no guesses
+    /// when opened from the middle
+    #[arg(long, conflicts_with_all = ["size", "variant", "headless", "network", "replay", "board"])]
+    pub daily: bool,
+
+    /// Race the bot, or a second player on the arrow keys, space and F, on a board beside
+    /// yours with the same mines and first click
+    #[arg(long, value_name = "RIVAL", conflicts_with_all = ["bot", "headless"])]
+    pub race: Option<Racer>,
+
+    /// Race another player over the network on the same board, or play together with --coop,
+    /// waiting for them to join on this port. The port is open on every network this machine
+    /// is on, so anyone who can reach the machine can join
+    #[arg(
+        long,
+        value_name = "PORT",
+        group = "network",
+        conflicts_with_all = ["bot", "race", "headless", "replay", "board"]
+    )]
+    pub host: Option<u16>,
+
+    /// Join a player hosting at this address, such as 192.168.1.20:4000, playing on their board
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        group = "network",
+        conflicts_with_all = ["host", "bot", "race", "headless", "replay", "board"]
+    )]
+    pub join: Option<String>,
+
+    /// Play together on one board with --host or --join, each with your own cursor and flags,
+    /// instead of racing
+    #[arg(long)]
+    pub coop: bool,
+
+    /// Mines a game with --coop can set off between everyone before it is lost, each one
+    /// after the first costing a life and being flagged instead
+    #[arg(long, value_name = "LIVES", default_value_t = 1, requires = "coop")]
+    pub lives: u8,
+
+    /// Play in the terminal instead of opening a window
+    #[arg(long, group = "headless")]
+    pub tui: bool,
+
+    /// Let a bot play over stdin and stdout, reading moves such as `reveal 3 4` and answering
+    /// each with the board as a line of JSON
+    #[arg(long, group = "headless", conflicts_with_all = ["tui", "replay", "bot"])]
+    pub protocol: bool,
+
+    /// Run this program as the bot and play over its stdin and stdout, as with --protocol
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        group = "headless",
+        conflicts_with_all = ["tui", "replay", "bot", "protocol"]
+    )]
+    pub bot_command: Option<String>,
+
+    /// Host games for bots connecting to this port on localhost, speaking the same protocol
+    /// as --protocol, and keep a scoreboard
+    #[arg(
+        long,
+        value_name = "PORT",
+        group = "headless",
+        conflicts_with_all = ["tui", "replay", "bot", "protocol", "bot_command"]
+    )]
+    pub serve: Option<u16>,
+
+    /// How long a bot connected to --serve has to answer before it forfeits the game
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 5.0,
+        requires = "serve"
+    )]
+    pub move_seconds: f32,
+
+    /// Close each bot's connection after this many games, sending it the scoreboard
+    #[arg(long, value_name = "GAMES", requires = "serve")]
+    pub games: Option<u32>,
+
+    /// Play back a replay saved at the end of a game
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["board", "size", "variant"]
+    )]
+    pub replay: Option<PathBuf>,
+
+    /// Play on the mines in a file, one row per line with `*` for a mine, `.` for a safe tile,
+    /// `-` for a safe tile that starts open and a digit for a tile holding that many mines
+    #[arg(long, value_name = "FILE", conflicts_with = "size")]
+    pub board: Option<PathBuf>,
+
+    /// Play through the puzzles in a pack file, moving between them with the left and right
+    /// arrows. Puzzles start partly open and have to be solved without a mistake
+    #[arg(long, value_name = "FILE", group = "practice")]
+    pub puzzles: Option<PathBuf>,
+
+    /// Practise reading patterns such as 1-2-1 in small positions, flagging the mines and
+    /// opening the safe tiles among those outlined
+    #[arg(long, group = "practice")]
+    pub train: bool,
+
+    /// Learn to play in short lessons that walk through revealing, flagging, chording and a
+    /// first pattern on boards laid out for them
+    #[arg(long, group = "practice", conflicts_with_all = ["fog", "bot"])]
+    pub tutorial: bool,
+
+    /// Check that every puzzle in the pack given with --puzzles can be solved by logic alone,
+    /// instead of playing them
+    #[arg(long, requires = "puzzles")]
+    pub check: bool,
+
+    /// Generate and solve this many boards of the chosen size without opening a window, one
+    /// for each seed counting up from --seed
+    #[arg(
+        long,
+        value_name = "BOARDS",
+        conflicts_with_all = ["variant", "headless", "network", "replay", "board", "bot", "race", "fog", "daily"]
+    )]
+    pub bench: Option<u64>,
+}
+
+impl Args {
+    /// Applies the flags on top of the settings from the config file, returning whether they
+    /// asked for a game to start straight away instead of the menu.
+    pub fn apply(&self, settings: &mut Settings) -> Result<bool> {
+        if self.bot {
+            settings.mode = Mode::Bot;
+        }
+
+        ensure!(
+            !self.coop || self.host.is_some() || self.join.is_some(),
+            "--coop needs --host or --join"
+        );
+        ensure!(self.lives > 0, "there has to be at least one life");
+        ensure!(
+            self.move_seconds > 0.0 && Duration::try_from_secs_f32(self.move_seconds).is_ok(),
+            "--move-seconds must be a number of seconds more than 0"
+        );
+
+        if self.daily {
+            settings.difficulty = Difficulty::Expert;
+        }
+
+        if let Some(racer) = self.race {
+            settings.mode = match racer {
+                Racer::Bot => Mode::RaceBot,
+                Racer::Keyboard => Mode::RaceKeyboard,
+            };
+        }
+
+        if self.torus {
+            settings.variant.topology = Topology::Torus;
+        }
+
+        if self.hex {
+            settings.variant.topology = Topology::Hex;
+        }
+
+        if self.triangle {
+            settings.variant.topology = Topology::Triangle;
+        }
+
+        if let Some(mines_per_tile) = self.mines_per_tile {
+            ensure!(
+                (1..=MAX_MINES_PER_TILE).contains(&mines_per_tile),
+                "mines per tile must be between 1 and {MAX_MINES_PER_TILE}"
+            );
+            settings.variant.mines_per_tile = mines_per_tile;
+        }
+
+        if let Some(clues) = self.clues {
+            settings.variant.clues = clues;
+        }
+
+        if let Some(fog) = self.fog {
+            settings.fog = fog;
+        }
+
+        if self.width.is_some() || self.height.is_some() || self.mines.is_some() {
+            let width = self.width.unwrap_or(settings.difficulty.width());
+            let height = self.height.unwrap_or(settings.difficulty.height());
+
+            ensure!(
+                (MIN_CUSTOM_SIZE..=MAX_CUSTOM_WIDTH).contains(&width),
+                "width must be between {MIN_CUSTOM_SIZE} and {MAX_CUSTOM_WIDTH}"
+            );
+            ensure!(
+                (MIN_CUSTOM_SIZE..=MAX_CUSTOM_HEIGHT).contains(&height),
+                "height must be between {MIN_CUSTOM_SIZE} and {MAX_CUSTOM_HEIGHT}"
+            );
+
+            let mines = self.mines.unwrap_or(settings.difficulty.mines());
+            let max_mines = settings.variant.max_mines(width, height);
+            ensure!(
+                (1..=max_mines).contains(&mines),
+                "a {width}x{height} board fits between 1 and {max_mines} mines"
+            );
+
+            settings.difficulty = Difficulty::custom(width, height, mines, settings.variant);
+            settings.custom = settings.difficulty;
+        }
+
+        Ok(self.width.is_some()
+            || self.height.is_some()
+            || self.mines.is_some()
+            || self.seed.is_some()
+            || self.daily
+            || self.bot
+            || self.race.is_some()
+            || self.host.is_some()
+            || self.join.is_some()
+            || self.torus
+            || self.hex
+            || self.triangle
+            || self.mines_per_tile.is_some()
+            || self.clues.is_some()
+            || self.fog.is_some()
+            || self.replay.is_some()
+            || self.board.is_some()
+            || self.puzzles.is_some()
+            || self.train
+            || self.tutorial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_for_one_run_are_not_saved() {
+        let config = Config::default();
+        let saved_before = config.settings();
+        let mut saved = config.settings();
+        let mut settings = config.settings();
+
+        let args = Args::parse_from([
+            "minesweeper",
+            "--torus",
+            "--fog",
+            "cursor",
+            "--bot",
+            "--clues",
+            "knight",
+            "--mines-per-tile",
+            "2",
+            "--width",
+            "20",
+        ]);
+        args.apply(&mut settings).unwrap();
+
+        // a change made in the settings menu during the run
+        let before = settings.clone();
+        settings.question_marks = true;
+        saved.keep_changes(&before, &settings);
+
+        let written = toml::to_string(&Config::new(&saved, theme::CLASSIC)).unwrap();
+        let read = toml::from_str::<Config>(&written).unwrap().settings();
+
+        assert!(read.question_marks);
+        assert_eq!(read.variant, saved_before.variant);
+        assert_eq!(read.fog, saved_before.fog);
+        assert_eq!(read.mode, saved_before.mode);
+        assert_eq!(read.difficulty, saved_before.difficulty);
+        assert_eq!(read.custom, saved_before.custom);
+    }
+
+    #[test]
+    fn flags_for_different_games_conflict() {
+        let parses = |flags: &[&str]| {
+            Args::try_parse_from(std::iter::once("minesweeper").chain(flags.iter().copied()))
+                .is_ok()
+        };
+
+        assert!(parses(&["--width", "20", "--height", "10", "--seed", "1"]));
+        assert!(parses(&[
+            "--hex",
+            "--clues",
+            "knight",
+            "--mines-per-tile",
+            "2"
+        ]));
+        assert!(parses(&["--host", "4000", "--coop", "--hex"]));
+        assert!(parses(&["--tutorial"]));
+        assert!(parses(&["--puzzles", "pack.txt", "--check"]));
+
+        for flags in [
+            &["--daily", "--width", "20"][..],
+            &["--daily", "--hex"],
+            &["--daily", "--join", "host:4000"],
+            &["--race", "bot", "--tui"],
+            &["--host", "4000", "--serve", "4001"],
+            &["--replay", "game.replay", "--clues", "liar"],
+            &["--board", "board.txt", "--seed", "1"],
+            &["--bench", "10", "--torus"],
+            &["--puzzles", "pack.txt", "--train"],
+            &["--train", "--mines", "10"],
+            &["--tutorial", "--protocol"],
+            &["--tutorial", "--fog", "recent"],
+            &["--train", "--host", "4000"],
+        ] {
+            assert!(!parses(flags), "{flags:?}");
+        }
+    }
+}
@@ -0,0 +1,652 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+use eyre::{bail, eyre, Result, WrapErr};
+use macroquad::prelude::*;
+
+use crate::{
+    grid::Grid,
+    outline_tile_shape,
+    race::draw_banner,
+    replay::Replay,
+    settings::{Settings, WrongFlags},
+    theme::Theme,
+    versus::draw_status,
+    Action, Board, Layout, State,
+};
+
+const PLAYER_COLOURS: [Color; 6] = [
+    Color::new(0.0, 0.45, 0.7, 1.0),
+    Color::new(0.84, 0.37, 0.0, 1.0),
+    Color::new(0.0, 0.62, 0.45, 1.0),
+    Color::new(0.8, 0.47, 0.65, 1.0),
+    Color::new(0.9, 0.62, 0.0, 1.0),
+    Color::new(0.34, 0.71, 0.91, 1.0),
+];
+
+const CURSOR_THICKNESS: f32 = 2.0;
+const FLAG_OWNER_THICKNESS: f32 = 1.0;
+
+// how long to wait before trying to connect again
+const RETRY: Duration = Duration::from_secs(1);
+
+/// What the network threads have to tell the game, with the player it came from. The host is
+/// always player 0.
+enum Event {
+    /// A player connected, with a handle for writing to them.
+    Connected(u8, TcpStream),
+    Line(u8, String),
+    Disconnected(u8),
+}
+
+/// Several players on one board over the network, each with their own cursor and flags in
+/// their own colour.
+///
+/// Everyone keeps a copy of the board. Players send the moves they want to the host, which puts
+/// them in order and sends every move made back to everyone, so all the copies make the same
+/// moves in the same order.
+///
+/// ```text
+/// you 2
+/// game 3 off keep size 9 9 10;seed 1234
+/// move 0 reveal 4 4
+/// move 2 flag 5 6
+/// cursor 1 3 3
+/// left 1
+/// ```
+///
+/// A game is sent as the lives it starts with, whether question marks are on, what happens to
+/// wrong flags and the replay of the untouched board with its lines joined by `;`. Players send
+/// moves as plain `reveal 4 4`, where their cursor is as `cursor 3 3`, and `new` to ask the
+/// host for a new game.
+///
+/// Moves reaching the host in the same frame are made flags first, so that a tile flagged and
+/// revealed at the same moment stays flagged, and then in player order. Only the first of them
+/// on each tile counts. While there is more than one life left, a mine that would go off is
+/// flagged instead at the cost of a life. Losing the last one ends the game for everyone.
+pub struct Coop {
+    host: bool,
+    /// Where the host is, as shown while waiting.
+    address: String,
+    events: Receiver<Event>,
+    /// The players connected to the host, or the host for everyone else.
+    peers: Vec<(u8, TcpStream)>,
+    /// Whether anyone was ever connected, so that waiting again reads as reconnecting.
+    connected_before: bool,
+    /// This player's number, given by the host when joining.
+    me: u8,
+    /// Lives a game starts with, set by the host.
+    starting_lives: u8,
+    lives: u8,
+    /// The host's rules, which every copy of the board makes moves by.
+    rules: Settings,
+    /// Moves made by hand here this frame, not yet put in order by the host.
+    requests: Vec<Action>,
+    /// Moves the host has been sent this frame, with whose they are.
+    pending: Vec<(u8, Action)>,
+    /// Every move of this game in the order the host made them, with whose they were.
+    moves: Vec<(u8, Action)>,
+    /// Who put the flags on each tile.
+    flag_owners: Grid<Option<u8>>,
+    /// Where everyone's cursor last was, this player's included.
+    cursors: Vec<(u8, (usize, usize))>,
+    /// The player who set off the mine that ended the game.
+    lost_by: Option<u8>,
+    /// The seed of the game being played, to notice a new one started here.
+    seed: u64,
+}
+
+impl Coop {
+    /// Hosts a game on `port`, on every network the machine is on. Every game starts with
+    /// `lives` lives shared between everyone.
+    pub fn host(port: u16, lives: u8, board: &Board, settings: &Settings) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))
+            .wrap_err_with(|| format!("listening on port {port}"))?;
+        let address = format!("port {}", listener.local_addr()?.port());
+        let (events, receiver) = mpsc::channel();
+
+        println!("waiting for players on {address}");
+        thread::spawn(move || {
+            // player 0 is the host, and numbers go round again once every one has been used
+            for (player, stream) in (1..=u8::MAX).cycle().zip(listener.incoming()) {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+
+                let events = events.clone();
+                thread::spawn(move || listen(stream, player, &events));
+            }
+        });
+
+        let mut coop = Self::new(true, address, receiver, board, settings);
+        coop.starting_lives = lives.max(1);
+        coop.lives = coop.starting_lives;
+        Ok(coop)
+    }
+
+    /// Joins the game hosted at `address`, such as `192.168.1.20:4000`, trying again until the
+    /// host is there.
+    pub fn join(address: String, board: &Board, settings: &Settings) -> Self {
+        let (events, receiver) = mpsc::channel();
+
+        let connecting_to = address.clone();
+        thread::spawn(move || loop {
+            match TcpStream::connect(&connecting_to) {
+                Ok(stream) => {
+                    if !listen(stream, 0, &events) {
+                        return;
+                    }
+                }
+                Err(_) => thread::sleep(RETRY),
+            }
+        });
+
+        Self::new(false, address, receiver, board, settings)
+    }
+
+    fn new(
+        host: bool,
+        address: String,
+        events: Receiver<Event>,
+        board: &Board,
+        settings: &Settings,
+    ) -> Self {
+        Self {
+            host,
+            address,
+            events,
+            peers: vec![],
+            connected_before: false,
+            me: 0,
+            starting_lives: 1,
+            lives: 1,
+            rules: settings.clone(),
+            requests: vec![],
+            pending: vec![],
+            moves: vec![],
+            flag_owners: Grid::new(board.width(), board.height(), None),
+            cursors: vec![],
+            lost_by: None,
+            seed: board.seed,
+        }
+    }
+
+    /// Asks for a move made by hand here, which is only made once the host has put it in order.
+    pub fn request(&mut self, action: Action) {
+        self.requests.push(action);
+    }
+
+    /// Swaps moves and cursors with the other players, then draws them over the board and
+    /// the lives left under it.
+    pub fn frame(
+        &mut self,
+        board: &mut Board,
+        layout: &Layout,
+        settings: &Settings,
+        theme: &Theme,
+    ) {
+        let (mouse_x, mouse_y) = mouse_position();
+        let (col, row) = layout.tile_at(mouse_x, mouse_y, board.width(), board.height());
+        let hovering = layout
+            .hovering_tile(mouse_x, mouse_y, col, row)
+            .then_some((col, row));
+
+        self.exchange(board, settings, hovering);
+        self.draw(board, layout, theme);
+    }
+
+    /// Plays what has come in and sends out what happened here, with the tile the mouse is on.
+    fn exchange(
+        &mut self,
+        board: &mut Board,
+        settings: &Settings,
+        hovering: Option<(usize, usize)>,
+    ) {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Event::Connected(player, peer) => self.connected(player, peer, board),
+                Event::Disconnected(player) => {
+                    self.peers.retain(|&(peer, _)| peer != player);
+
+                    if self.host {
+                        println!("player {} left", player + 1);
+                        self.cursors.retain(|&(other, _)| other != player);
+                        self.broadcast(&format!("left {player}"));
+                    } else {
+                        println!("lost the connection to {}", self.address);
+                        self.cursors.clear();
+                    }
+                }
+                Event::Line(player, line) => {
+                    if let Err(error) = self.receive(player, &line, board, settings) {
+                        eprintln!("ignoring {line:?} from player {}: {error}", player + 1);
+                    }
+                }
+            }
+        }
+
+        // a new game started here, from the smiley or the menu
+        if board.seed != self.seed {
+            if self.host {
+                self.start(board, settings);
+            } else {
+                self.reset(board);
+                self.broadcast("new");
+            }
+        }
+
+        let requests = std::mem::take(&mut self.requests);
+        if self.host {
+            self.pending
+                .extend(requests.into_iter().map(|action| (self.me, action)));
+            self.make_pending(board);
+        } else {
+            for action in requests {
+                self.broadcast(&action.to_string());
+            }
+        }
+
+        let cursor = self.cursor(self.me);
+        if let Some((col, row)) = hovering.filter(|&hovering| Some(hovering) != cursor) {
+            self.move_cursor(self.me, (col, row));
+
+            if self.host {
+                self.broadcast(&format!("cursor {} {col} {row}", self.me));
+            } else {
+                self.broadcast(&format!("cursor {col} {row}"));
+            }
+        }
+    }
+
+    fn connected(&mut self, player: u8, mut peer: TcpStream, board: &Board) {
+        self.connected_before = true;
+
+        if !self.host {
+            println!("joined the game at {}", self.address);
+            self.peers = vec![(player, peer)];
+            self.cursors.clear();
+            return;
+        }
+
+        println!("player {} joined", player + 1);
+
+        // everything the player needs to catch up: who they are, the game so far and where
+        // everyone is
+        let mut lines = vec![format!("you {player}"), self.game_line(board)];
+        lines.extend(
+            self.moves
+                .iter()
+                .map(|(mover, action)| format!("move {mover} {action}")),
+        );
+        lines.extend(
+            self.cursors
+                .iter()
+                .map(|(other, (col, row))| format!("cursor {other} {col} {row}")),
+        );
+
+        if lines.iter().all(|line| writeln!(peer, "{line}").is_ok()) {
+            self.peers.push((player, peer));
+        }
+    }
+
+    fn receive(
+        &mut self,
+        player: u8,
+        line: &str,
+        board: &mut Board,
+        settings: &Settings,
+    ) -> Result<()> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+
+        if self.host {
+            match words[..] {
+                ["cursor", col, row] => {
+                    let tile = on_board(board, col.parse()?, row.parse()?)?;
+                    self.move_cursor(player, tile);
+                    self.broadcast(&format!("cursor {player} {} {}", tile.0, tile.1));
+                }
+                ["new"] => *board = board.restart(),
+                _ => {
+                    let action = line.parse::<Action>()?;
+                    if action == Action::Solve {
+                        bail!("the bot doesn't play shared boards");
+                    }
+
+                    self.pending.push((player, action));
+                }
+            }
+
+            return Ok(());
+        }
+
+        match words[..] {
+            ["you", player] => self.me = player.parse()?,
+            ["game", lives, question_marks, wrong_flags, ..] => {
+                let replay = line
+                    .splitn(5, ' ')
+                    .nth(4)
+                    .ok_or_else(|| eyre!("the game has no board"))?
+                    .replace(';', "\n")
+                    .parse::<Replay>()?;
+
+                self.starting_lives = lives.parse()?;
+                self.rules = settings.clone();
+                self.rules.question_marks = question_marks == "on";
+                self.rules.wrong_flags = match wrong_flags {
+                    "clear" => WrongFlags::Clear,
+                    _ => WrongFlags::Keep,
+                };
+
+                *board = replay.board();
+                self.reset(board);
+            }
+            ["move", mover, ..] => {
+                let action = line
+                    .splitn(3, ' ')
+                    .nth(2)
+                    .ok_or_else(|| eyre!("the move is missing"))?
+                    .parse::<Action>()?;
+
+                self.play(board, mover.parse()?, action);
+            }
+            ["cursor", other, col, row] => {
+                let tile = on_board(board, col.parse()?, row.parse()?)?;
+                self.move_cursor(other.parse()?, tile);
+            }
+            ["left", other] => {
+                let other = other.parse::<u8>()?;
+                self.cursors.retain(|&(player, _)| player != other);
+            }
+            _ => bail!("not something the host sends"),
+        }
+
+        Ok(())
+    }
+
+    /// The rules every copy of the board makes moves by.
+    pub fn rules(&self) -> &Settings {
+        &self.rules
+    }
+
+    /// Starts a new game on the host, with its rules as they are now, and sends it to everyone.
+    fn start(&mut self, board: &Board, settings: &Settings) {
+        self.rules = settings.clone();
+        self.reset(board);
+
+        let game = self.game_line(board);
+        self.broadcast(&game);
+    }
+
+    fn reset(&mut self, board: &Board) {
+        self.seed = board.seed;
+        self.lives = self.starting_lives;
+        self.pending.clear();
+        self.moves.clear();
+        self.flag_owners = Grid::new(board.width(), board.height(), None);
+        self.lost_by = None;
+    }
+
+    fn game_line(&self, board: &Board) -> String {
+        let fresh = Replay::new(&board.reseeded(board.seed), &self.rules).to_string();
+
+        format!(
+            "game {} {} {} {}",
+            self.starting_lives,
+            if self.rules.question_marks {
+                "on"
+            } else {
+                "off"
+            },
+            match self.rules.wrong_flags {
+                WrongFlags::Keep => "keep",
+                WrongFlags::Clear => "clear",
+            },
+            fresh.trim_end().replace('\n', ";")
+        )
+    }
+
+    /// Puts the moves that reached the host this frame in order, then makes and sends them.
+    fn make_pending(&mut self, board: &mut Board) {
+        let mut pending = std::mem::take(&mut self.pending);
+
+        // sorting is stable, so each player's own moves keep their order
+        pending.sort_by_key(|&(player, action)| (!matches!(action, Action::Flag(..)), player));
+
+        let mut played = vec![];
+        for (player, action) in pending {
+            let (Action::Reveal(col, row) | Action::Flag(col, row) | Action::Chord(col, row)) =
+                action
+            else {
+                continue;
+            };
+
+            if played.contains(&(col, row)) {
+                continue;
+            }
+            played.push((col, row));
+
+            if self.play(board, player, action) {
+                self.moves.push((player, action));
+                self.broadcast(&format!("move {player} {action}"));
+            }
+        }
+    }
+
+    /// Makes a move on this copy of the board, returning whether it did anything.
+    fn play(&mut self, board: &mut Board, player: u8, action: Action) -> bool {
+        let (Action::Reveal(col, row) | Action::Flag(col, row) | Action::Chord(col, row)) = action
+        else {
+            return false;
+        };
+
+        if col >= board.width() || row >= board.height() {
+            return false;
+        }
+
+        let moves = board.history.len();
+        let lives = self.lives;
+        let mut mines = vec![];
+
+        if self.lives > 1 && board.state == State::Playing {
+            mines = match action {
+                Action::Reveal(..) if !board.flagged(col, row) && board.mine(col, row) => {
+                    vec![(col, row)]
+                }
+                Action::Chord(..) if board.revealed(col, row) && board.satisfied(col, row) => board
+                    .surrounding_tiles(col, row)
+                    .filter(|&(col, row)| !board.flagged(col, row) && board.mine(col, row))
+                    .collect(),
+                _ => vec![],
+            };
+
+            if !mines.is_empty() {
+                self.lives -= 1;
+            }
+
+            for &(col, row) in &mines {
+                board.set_flagged(col, row, board.tiles[(col, row)].mines);
+                self.flag_owners[(col, row)] = Some(player);
+            }
+        }
+
+        match action {
+            // the flags just put on the mines leave the number with more flags than it says,
+            // so the chord would do nothing, and its safe tiles are opened one by one instead
+            Action::Chord(..) if !mines.is_empty() => {
+                for (col, row) in board.surrounding_tiles(col, row) {
+                    if !board.flagged(col, row) && !board.revealed(col, row) {
+                        board.apply(Action::Reveal(col, row), &self.rules);
+                    }
+                }
+            }
+            _ => board.apply(action, &self.rules),
+        }
+        board.settle();
+
+        if let Action::Flag(..) = action {
+            self.flag_owners[(col, row)] = board.flagged(col, row).then_some(player);
+        }
+
+        if board.state == State::Dead && self.lost_by.is_none() {
+            self.lost_by = Some(player);
+        }
+
+        board.history.len() > moves || self.lives < lives
+    }
+
+    fn cursor(&self, player: u8) -> Option<(usize, usize)> {
+        self.cursors
+            .iter()
+            .find(|&&(other, _)| other == player)
+            .map(|&(_, tile)| tile)
+    }
+
+    fn move_cursor(&mut self, player: u8, tile: (usize, usize)) {
+        self.cursors.retain(|&(other, _)| other != player);
+        self.cursors.push((player, tile));
+    }
+
+    /// Sends a line to everyone connected, dropping anyone it can't reach.
+    fn broadcast(&mut self, line: &str) {
+        self.peers
+            .retain_mut(|(_, peer)| writeln!(peer, "{line}").is_ok());
+    }
+
+    fn draw(&self, board: &Board, layout: &Layout, theme: &Theme) {
+        let scale = layout.scale;
+
+        for (col, row) in board.tiles.positions() {
+            if let Some(player) = self.flag_owners[(col, row)].filter(|_| board.flagged(col, row)) {
+                let thickness = FLAG_OWNER_THICKNESS * scale;
+                outline_tile_shape(layout, col, row, thickness, colour(player));
+            }
+        }
+
+        if board.state == State::Playing || board.state == State::NewGame {
+            for &(player, (col, row)) in &self.cursors {
+                // cursors from before the board changed size can be off it for a moment
+                if player != self.me && col < board.width() && row < board.height() {
+                    let thickness = CURSOR_THICKNESS * scale;
+                    outline_tile_shape(layout, col, row, thickness, colour(player));
+                }
+            }
+        }
+
+        let status = if !self.peers.is_empty() {
+            match self.lives {
+                1 => format!("Player {}, last life", self.me + 1),
+                lives => format!("Player {}, {lives} lives", self.me + 1),
+            }
+        } else if !self.connected_before {
+            match self.host {
+                true => format!("Waiting on {}", self.address),
+                false => "Connecting...".to_owned(),
+            }
+        } else {
+            match self.host {
+                true => "Everyone left".to_owned(),
+                false => "Reconnecting...".to_owned(),
+            }
+        };
+        let lives = self.lives as f32 / self.starting_lives as f32;
+        draw_status(&status, lives, colour(self.me), layout, &theme.colours);
+
+        if let Some(player) = self.lost_by.filter(|_| board.state == State::Dead) {
+            let text = if player == self.me {
+                "You hit a mine".to_owned()
+            } else {
+                format!("Player {} hit a mine", player + 1)
+            };
+            draw_banner(&text, layout.window_width / 2.0, layout, &theme.colours);
+        }
+    }
+}
+
+fn colour(player: u8) -> Color {
+    PLAYER_COLOURS[player as usize % PLAYER_COLOURS.len()]
+}
+
+fn on_board(board: &Board, col: usize, row: usize) -> Result<(usize, usize)> {
+    if col >= board.width() || row >= board.height() {
+        bail!("{col} {row} is off the board");
+    }
+
+    Ok((col, row))
+}
+
+/// Passes on what `player` sends until they go, returning whether the game is still there to
+/// pass it to.
+fn listen(stream: TcpStream, player: u8, events: &Sender<Event>) -> bool {
+    let _ = stream.set_nodelay(true);
+
+    let Ok(writer) = stream.try_clone() else {
+        return true;
+    };
+
+    if events.send(Event::Connected(player, writer)).is_err() {
+        return false;
+    }
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        if events.send(Event::Line(player, line)).is_err() {
+            return false;
+        }
+    }
+
+    events.send(Event::Disconnected(player)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::layout_board;
+
+    fn coop(host: bool, board: &Board) -> Coop {
+        let (_, receiver) = mpsc::channel();
+        let mut coop = Coop::new(host, String::new(), receiver, board, &Settings::default());
+        coop.lives = 2;
+        coop
+    }
+
+    #[test]
+    fn a_chord_onto_a_mine_still_opens_its_safe_tiles() {
+        let (layout, _) = crate::replay::parse_rows(&["*..", "...", "..."]).unwrap();
+        let mut board = layout_board(&layout, None, Default::default());
+        let mut coop = coop(true, &board);
+
+        coop.play(&mut board, 0, Action::Reveal(1, 1));
+        // a wrong flag satisfies the 1, leaving its mine hidden
+        coop.play(&mut board, 0, Action::Flag(2, 0));
+        assert!(coop.play(&mut board, 0, Action::Chord(1, 1)));
+
+        assert_eq!(coop.lives, 1);
+        assert_eq!(board.state, State::Playing);
+        assert!(board.flagged(0, 0));
+        assert!(board
+            .tiles
+            .positions()
+            .filter(|&tile| tile != (0, 0) && tile != (2, 0))
+            .all(|(col, row)| board.revealed(col, row)));
+    }
+
+    #[test]
+    fn a_game_too_big_to_play_is_refused() {
+        let mut board =
+            crate::new_board(crate::settings::Difficulty::Beginner, Default::default(), 1);
+        let mut coop = coop(false, &board);
+        let settings = Settings::default();
+
+        let line = "game 3 off keep size 100000 100000 5;seed 1";
+        assert!(coop.receive(0, line, &mut board, &settings).is_err());
+        assert_eq!(board.width(), 9);
+    }
+}
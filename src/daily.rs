@@ -0,0 +1,201 @@
+use macroquad::miniquad;
+
+use crate::{
+    bitboard::BitGame,
+    grid::Grid,
+    new_board,
+    settings::{Difficulty, Variant},
+    stats::{format_date, unix_now, DailyResult, Stats},
+    Board, State,
+};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+/// Columns and rows of tiles each square of the shared grid stands for.
+const SHARE_BLOCK: (usize, usize) = (3, 4);
+
+/// Days since 1970-01-01. Days start at midnight UTC, so everyone is on the same board at once.
+pub fn today() -> u64 {
+    unix_now() / SECONDS_PER_DAY
+}
+
+/// The only tile the daily board can be opened from, in the middle of the board.
+pub fn start_tile() -> (usize, usize) {
+    (
+        Difficulty::Expert.width() / 2,
+        Difficulty::Expert.height() / 2,
+    )
+}
+
+/// The expert board for `day`, the same for everyone who plays it that day.
+pub fn board(day: u64) -> Board {
+    Board {
+        daily: Some(day),
+        ..new_board(Difficulty::Expert, Variant::default(), seed(day))
+    }
+}
+
+/// Tries seeds in an order fixed by the day until one places mines that the bot's simplest
+/// rules clear from the start tile, so the board never needs a guess.
+fn seed(day: u64) -> u64 {
+    let (col, row) = start_tile();
+    let mut seed = mix(day);
+
+    while !BitGame::generate(
+        Difficulty::Expert,
+        col,
+        row,
+        &mut fastrand::Rng::with_seed(seed),
+    )
+    .solve()
+    {
+        seed = seed.wrapping_add(1);
+    }
+
+    seed
+}
+
+/// Spreads the days out over the seeds, so that one day's search never runs into the next's.
+fn mix(day: u64) -> u64 {
+    let mut z = day.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Records the result of the day's board if it was the first game on it, and prints and copies
+/// a summary to share with the others.
+pub fn finish(board: &Board, day: u64, seconds: f32, stats: &mut Stats) -> Vec<String> {
+    let first = stats.record_daily(DailyResult {
+        day,
+        won: board.state == State::Won,
+        seconds,
+        bbbv: bbbv(board),
+    });
+
+    let share = share(board, day, seconds, stats.daily_streak(day));
+    miniquad::window::clipboard_set(&share);
+
+    let mut notes = vec![share, "copied to the clipboard".to_owned()];
+    if !first {
+        notes.push("only the first game on a daily board counts".to_owned());
+    }
+
+    notes
+}
+
+/// The fewest clicks that clear the board: one for each opening, and one for every safe tile
+/// not on the edge of an opening.
+fn bbbv(board: &Board) -> usize {
+    let safe = |tile: (usize, usize)| !board.mine(tile.0, tile.1);
+    let zero = |tile: (usize, usize)| safe(tile) && board.neighbour_mines(tile.0, tile.1) == 0;
+    let mut counted = Grid::new(board.width(), board.height(), false);
+    let mut clicks = 0;
+
+    for tile in board.tiles.positions() {
+        if !zero(tile) || counted[tile] {
+            continue;
+        }
+
+        clicks += 1;
+        counted[tile] = true;
+        let mut stack = vec![tile];
+
+        while let Some((col, row)) = stack.pop() {
+            for neighbour in board.surrounding_tiles(col, row) {
+                if safe(neighbour) && !counted[neighbour] {
+                    counted[neighbour] = true;
+
+                    if zero(neighbour) {
+                        stack.push(neighbour);
+                    }
+                }
+            }
+        }
+    }
+
+    clicks
+        + board
+            .tiles
+            .positions()
+            .filter(|&tile| safe(tile) && !counted[tile])
+            .count()
+}
+
+/// The date, how the game went and the streak, over a rough map of how much of the board was
+/// cleared that gives nothing away about where the mines are.
+fn share(board: &Board, day: u64, seconds: f32, streak: usize) -> String {
+    let result = match board.state {
+        State::Won if seconds > 0.0 => format!(
+            "✅ {seconds:.3}s, {:.2} 3BV/s",
+            bbbv(board) as f32 / seconds
+        ),
+        // a board cleared within the clock's resolution has no rate worth sharing
+        State::Won => format!("✅ {seconds:.3}s"),
+        _ => format!("💥 after {seconds:.3}s"),
+    };
+
+    let mut lines = vec![
+        format!("Minesweeper daily {}", format_date(day * SECONDS_PER_DAY)),
+        format!("{result}, streak {streak}"),
+    ];
+
+    let (block_width, block_height) = SHARE_BLOCK;
+    for top in (0..board.height()).step_by(block_height) {
+        let mut line = String::new();
+
+        for left in (0..board.width()).step_by(block_width) {
+            let tiles = (top..(top + block_height).min(board.height()))
+                .flat_map(|row| {
+                    (left..(left + block_width).min(board.width())).map(move |col| (col, row))
+                })
+                .collect::<Vec<_>>();
+            let safe = tiles
+                .iter()
+                .filter(|&&(col, row)| !board.mine(col, row))
+                .count();
+            let cleared = tiles
+                .iter()
+                .filter(|&&(col, row)| !board.mine(col, row) && board.revealed(col, row))
+                .count();
+            let exploded = tiles
+                .iter()
+                .any(|tile| board.unflagged_mines.contains(tile));
+
+            line.push(if exploded {
+                '💥'
+            } else if cleared == safe && (safe > 0 || board.state == State::Won) {
+                '🟩'
+            } else if cleared > 0 {
+                '🟨'
+            } else {
+                '⬛'
+            });
+        }
+
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{replay, settings::Settings, Action};
+
+    #[test]
+    fn an_instant_win_shares_no_rate() {
+        let (layout, _) = replay::parse_rows(&["....", "....", "...*"]).unwrap();
+        let mut board = replay::layout_board(&layout, None, Variant::default());
+        board.apply(Action::Reveal(0, 0), &Settings::default());
+        board.settle();
+        assert_eq!(board.state, State::Won);
+
+        let instant = share(&board, 0, 0.0, 1);
+        assert!(
+            !instant.contains("inf") && !instant.contains("NaN"),
+            "{instant}"
+        );
+        assert!(share(&board, 0, 2.0, 1).contains("0.50 3BV/s"));
+    }
+}
@@ -0,0 +1,512 @@
+use std::ops::{Index, IndexMut};
+
+use serde::{Deserialize, Serialize};
+
+/// Offsets of the eight tiles around a tile, going round clockwise from the top left.
+const NEIGHBOUR_OFFSETS: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+];
+
+/// Offsets of the six tiles around a hex, clockwise from the top left. Odd rows sit half a tile
+/// to the right of even rows, so the rows above and below lean the other way on odd rows.
+const HEX_EVEN_ROW_OFFSETS: [(isize, isize); 6] =
+    [(-1, -1), (0, -1), (1, 0), (0, 1), (-1, 1), (-1, 0)];
+const HEX_ODD_ROW_OFFSETS: [(isize, isize); 6] =
+    [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 0)];
+
+/// Offsets of the twelve triangles sharing a side or a corner with a triangle pointing up,
+/// clockwise from the top left. Three meet at its top corner and five along its base.
+const TRIANGLE_UP_OFFSETS: [(isize, isize); 12] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (2, 0),
+    (2, 1),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-2, 1),
+    (-2, 0),
+    (-1, 0),
+];
+/// The same for a triangle pointing down, which is the one pointing up upside down.
+const TRIANGLE_DOWN_OFFSETS: [(isize, isize); 12] = [
+    (-2, -1),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (2, -1),
+    (2, 0),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-2, 0),
+];
+
+/// Offsets of the eight tiles a chess knight's move away, clockwise from the top left.
+const KNIGHT_OFFSETS: [(isize, isize); 8] = [
+    (-1, -2),
+    (1, -2),
+    (2, -1),
+    (2, 1),
+    (1, 2),
+    (-1, 2),
+    (-2, 1),
+    (-2, -1),
+];
+
+/// Offsets of the tiles up to two away in a straight line up, down, left or right.
+const CROSS_OFFSETS: [(isize, isize); 8] = [
+    (0, -2),
+    (0, -1),
+    (1, 0),
+    (2, 0),
+    (0, 1),
+    (0, 2),
+    (-1, 0),
+    (-2, 0),
+];
+
+/// The shape of the tiles and how the edges of the board join up.
+#[derive(PartialEq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Topology {
+    #[default]
+    Flat,
+    /// The left edge wraps round to the right and the top to the bottom, so every tile has
+    /// eight neighbours. Boards less than three tiles across can't wrap, and are played flat.
+    Torus,
+    /// Hexagons with six neighbours, pointing up, with every odd row shifted half a tile right.
+    Hex,
+    /// Triangles pointing alternately up and down, starting with up in the top left. Each has
+    /// twelve neighbours: three sharing its sides and nine more sharing only a corner.
+    Triangle,
+}
+
+impl Topology {
+    pub fn next(self) -> Self {
+        match self {
+            Topology::Flat => Topology::Torus,
+            Topology::Torus => Topology::Hex,
+            Topology::Hex => Topology::Triangle,
+            Topology::Triangle => Topology::Flat,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Topology::Flat => "Flat",
+            Topology::Torus => "Torus",
+            Topology::Hex => "Hex",
+            Topology::Triangle => "Triangle",
+        }
+    }
+
+    pub const ALL: [Topology; 4] = [
+        Topology::Flat,
+        Topology::Torus,
+        Topology::Hex,
+        Topology::Triangle,
+    ];
+
+    /// Whether the tiles are squares.
+    pub fn square(self) -> bool {
+        matches!(self, Topology::Flat | Topology::Torus)
+    }
+
+    /// Offsets from a tile to the tiles around it, whether or not they are on the board.
+    pub fn neighbour_offsets(self, col: usize, row: usize) -> &'static [(isize, isize)] {
+        match self {
+            Topology::Flat | Topology::Torus => &NEIGHBOUR_OFFSETS,
+            Topology::Hex if row % 2 == 1 => &HEX_ODD_ROW_OFFSETS,
+            Topology::Hex => &HEX_EVEN_ROW_OFFSETS,
+            Topology::Triangle if pointing_up(col, row) => &TRIANGLE_UP_OFFSETS,
+            Topology::Triangle => &TRIANGLE_DOWN_OFFSETS,
+        }
+    }
+
+    /// The tile `col_offset` columns right and `row_offset` rows down from a tile, if there is
+    /// one on a `width` by `height` board. Only the torus wraps round.
+    pub fn offset(
+        self,
+        (col, row): (usize, usize),
+        (col_offset, row_offset): (isize, isize),
+        width: usize,
+        height: usize,
+    ) -> Option<(usize, usize)> {
+        if self == Topology::Torus && width >= 3 && height >= 3 {
+            Some((
+                (col as isize + col_offset).rem_euclid(width as isize) as usize,
+                (row as isize + row_offset).rem_euclid(height as isize) as usize,
+            ))
+        } else {
+            let col = col.checked_add_signed(col_offset)?;
+            let row = row.checked_add_signed(row_offset)?;
+            (col < width && row < height).then_some((col, row))
+        }
+    }
+}
+
+/// What the numbers on revealed tiles count. Whatever the rule, a tile's clue covers the same
+/// tiles that opening up empty space spreads to and that chording reveals.
+#[derive(PartialEq, Debug, Default, Clone, Copy, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Clues {
+    /// Mines in the tiles around, as the shape of the tiles has it.
+    #[default]
+    Classic,
+    /// Mines a chess knight's move away. Only squares have knight's moves, so other shapes of
+    /// tile count the tiles around as usual.
+    Knight,
+    /// Mines up to two tiles away in a straight line across a side, on squares.
+    Cross,
+    /// The tiles around as usual, but every number is one more or one less than the truth.
+    /// Empty space never opens up on its own, since that would give the truth away.
+    Liar,
+}
+
+impl Clues {
+    pub fn next(self) -> Self {
+        match self {
+            Clues::Classic => Clues::Knight,
+            Clues::Knight => Clues::Cross,
+            Clues::Cross => Clues::Liar,
+            Clues::Liar => Clues::Classic,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Clues::Classic => "Classic",
+            Clues::Knight => "Knight",
+            Clues::Cross => "Cross",
+            Clues::Liar => "Liar",
+        }
+    }
+
+    pub const ALL: [Clues; 4] = [Clues::Classic, Clues::Knight, Clues::Cross, Clues::Liar];
+
+    /// Offsets from a tile to the tiles its clue counts the mines in.
+    pub fn offsets(self, topology: Topology, col: usize, row: usize) -> &'static [(isize, isize)] {
+        match self {
+            Clues::Knight if topology.square() => &KNIGHT_OFFSETS,
+            Clues::Cross if topology.square() => &CROSS_OFFSETS,
+            _ => topology.neighbour_offsets(col, row),
+        }
+    }
+
+    /// How many columns or rows away from a tile the furthest tile its clue counts is. The
+    /// shapes are the same for every tile, so the tile at the top left speaks for them all.
+    pub fn reach(self, topology: Topology) -> usize {
+        self.offsets(topology, 0, 0)
+            .iter()
+            .map(|&(col, row)| col.unsigned_abs().max(row.unsigned_abs()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The number shown for a tile whose clue tiles hold `mines` mines.
+    pub fn shown(self, mines: u8, rng: &mut fastrand::Rng) -> u8 {
+        match self {
+            Clues::Liar if mines == 0 || rng.bool() => mines + 1,
+            Clues::Liar => mines - 1,
+            _ => mines,
+        }
+    }
+
+    /// Every number of mines that could be behind a clue showing `shown`.
+    pub fn mine_counts(self, shown: u8) -> impl Iterator<Item = u8> + Clone {
+        match self {
+            Clues::Liar => [shown.checked_sub(1), shown.checked_add(1)],
+            _ => [Some(shown), None],
+        }
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// Whether the triangle at a tile points up on a [`Topology::Triangle`] board.
+pub fn pointing_up(col: usize, row: usize) -> bool {
+    (col + row).is_multiple_of(2)
+}
+
+/// A rectangle of cells kept row by row in a single allocation, indexed by `(col, row)`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![value; width * height],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn index(&self, col: usize, row: usize) -> usize {
+        debug_assert!(col < self.width && row < self.height);
+        row * self.width + col
+    }
+
+    pub fn neighbours(&self, col: usize, row: usize, topology: Topology) -> Neighbours {
+        let offsets = topology.neighbour_offsets(col, row);
+        Neighbours::new(col, row, self.width, self.height, topology, offsets)
+    }
+
+    /// The tiles whose mines the clue on a tile counts.
+    pub fn clue_tiles(
+        &self,
+        col: usize,
+        row: usize,
+        topology: Topology,
+        clues: Clues,
+    ) -> Neighbours {
+        let offsets = clues.offsets(topology, col, row);
+        Neighbours::new(col, row, self.width, self.height, topology, offsets)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.cells.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.cells.iter_mut()
+    }
+
+    /// Every `(col, row)` in the grid, in the order the cells are stored.
+    pub fn positions(&self) -> impl Iterator<Item = (usize, usize)> {
+        let width = self.width;
+        (0..self.len()).map(move |index| (index % width, index / width))
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (col, row): (usize, usize)) -> &T {
+        &self.cells[Grid::index(self, col, row)]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (col, row): (usize, usize)) -> &mut T {
+        let index = Grid::index(self, col, row);
+        &mut self.cells[index]
+    }
+}
+
+/// The tiles on the board at some offsets from a tile.
+#[derive(Clone)]
+pub struct Neighbours {
+    col: usize,
+    row: usize,
+    width: usize,
+    height: usize,
+    topology: Topology,
+    offsets: &'static [(isize, isize)],
+    next: usize,
+}
+
+impl Neighbours {
+    pub fn new(
+        col: usize,
+        row: usize,
+        width: usize,
+        height: usize,
+        topology: Topology,
+        offsets: &'static [(isize, isize)],
+    ) -> Self {
+        Self {
+            col,
+            row,
+            width,
+            height,
+            topology,
+            offsets,
+            next: 0,
+        }
+    }
+}
+
+impl Iterator for Neighbours {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        while let Some(&offset) = self.offsets.get(self.next) {
+            self.next += 1;
+
+            let neighbour =
+                self.topology
+                    .offset((self.col, self.row), offset, self.width, self.height);
+
+            if neighbour.is_some() {
+                return neighbour;
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn neighbours(
+        grid: &Grid<()>,
+        col: usize,
+        row: usize,
+        topology: Topology,
+    ) -> Vec<(usize, usize)> {
+        let mut tiles = grid.neighbours(col, row, topology).collect::<Vec<_>>();
+        tiles.sort();
+        tiles
+    }
+
+    #[test]
+    fn tiles_away_from_the_edges_have_every_neighbour() {
+        let grid = Grid::new(8, 8, ());
+
+        for (topology, even, odd) in [
+            (Topology::Flat, 8, 8),
+            (Topology::Torus, 8, 8),
+            (Topology::Hex, 6, 6),
+            (Topology::Triangle, 12, 12),
+        ] {
+            assert_eq!(
+                neighbours(&grid, 4, 4, topology).len(),
+                even,
+                "{topology:?}"
+            );
+            assert_eq!(neighbours(&grid, 4, 3, topology).len(), odd, "{topology:?}");
+        }
+    }
+
+    #[test]
+    fn hex_rows_lean_towards_the_row_in_between() {
+        let grid = Grid::new(8, 8, ());
+
+        // odd rows sit half a tile right, so an even row reaches left and an odd row right
+        assert_eq!(
+            neighbours(&grid, 4, 4, Topology::Hex),
+            [(3, 3), (3, 4), (3, 5), (4, 3), (4, 5), (5, 4)]
+        );
+        assert_eq!(
+            neighbours(&grid, 4, 3, Topology::Hex),
+            [(3, 3), (4, 2), (4, 4), (5, 2), (5, 3), (5, 4)]
+        );
+    }
+
+    #[test]
+    fn triangles_take_five_from_their_base_and_three_from_their_point() {
+        let grid = Grid::new(8, 8, ());
+
+        assert!(pointing_up(4, 4));
+        let up = neighbours(&grid, 4, 4, Topology::Triangle);
+        assert_eq!(up.iter().filter(|&&(_, row)| row == 5).count(), 5);
+        assert_eq!(up.iter().filter(|&&(_, row)| row == 3).count(), 3);
+
+        assert!(!pointing_up(4, 3));
+        let down = neighbours(&grid, 4, 3, Topology::Triangle);
+        assert_eq!(down.iter().filter(|&&(_, row)| row == 2).count(), 5);
+        assert_eq!(down.iter().filter(|&&(_, row)| row == 4).count(), 3);
+    }
+
+    #[test]
+    fn neighbours_are_neighbours_both_ways() {
+        let grid = Grid::new(7, 6, ());
+
+        for topology in Topology::ALL {
+            for (col, row) in grid.positions() {
+                let tiles = neighbours(&grid, col, row, topology);
+                let mut unique = tiles.clone();
+                unique.dedup();
+
+                assert_eq!(tiles, unique, "{topology:?} at {col} {row}");
+                assert!(!tiles.contains(&(col, row)));
+                for &(other_col, other_row) in &tiles {
+                    assert!(
+                        neighbours(&grid, other_col, other_row, topology).contains(&(col, row)),
+                        "{topology:?} at {col} {row} and {other_col} {other_row}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_the_torus_wraps_round_the_edges() {
+        let grid = Grid::new(5, 4, ());
+
+        assert_eq!(
+            neighbours(&grid, 0, 0, Topology::Flat),
+            [(0, 1), (1, 0), (1, 1)]
+        );
+        assert_eq!(neighbours(&grid, 0, 0, Topology::Hex), [(0, 1), (1, 0)]);
+        assert_eq!(neighbours(&grid, 0, 0, Topology::Triangle).len(), 5);
+
+        let corner = neighbours(&grid, 0, 0, Topology::Torus);
+        assert_eq!(corner.len(), 8);
+        assert!(corner.contains(&(4, 3)));
+
+        assert_eq!(Topology::Torus.offset((0, 0), (-2, 1), 5, 4), Some((3, 1)));
+        assert_eq!(Topology::Flat.offset((0, 0), (-2, 1), 5, 4), None);
+        // a torus too narrow to wrap without a tile meeting itself is played flat
+        assert_eq!(Topology::Torus.offset((0, 0), (-1, 0), 2, 4), None);
+    }
+
+    #[test]
+    fn clues_on_the_smallest_torus_count_each_tile_once() {
+        for clues in Clues::ALL {
+            let size = 2 * clues.reach(Topology::Torus) + 1;
+            let grid = Grid::new(size, size, ());
+
+            for (col, row) in grid.positions() {
+                let mut tiles = grid
+                    .clue_tiles(col, row, Topology::Torus, clues)
+                    .collect::<Vec<_>>();
+                tiles.sort();
+                tiles.dedup();
+
+                assert_eq!(tiles.len(), 8, "{clues:?} at {col} {row}");
+                assert!(!tiles.contains(&(col, row)));
+            }
+        }
+    }
+}
@@ -1,594 +1,2147 @@
-use std::{collections::VecDeque, time::Instant};
-
+use std::{
+    collections::VecDeque,
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use config::{Args, Config};
+use coop::Coop;
+use eyre::bail;
+use grid::{pointing_up, Clues, Grid, Neighbours, Topology};
 use macroquad::prelude::*;
-
-const EXPERT_WIDTH: usize = 30;
-const EXPERT_HEIGHT: usize = 16;
-const EXPERT_MINES: usize = 99;
-// const EXPERT_MINES: usize = 20;
-const WINDOW_SIZE_MULTIPLIER: f32 = 1.5;
-const WINDOW_WIDTH: i32 = (505.0 * WINDOW_SIZE_MULTIPLIER) as i32;
-const WINDOW_HEIGHT: i32 = (324.0 * WINDOW_SIZE_MULTIPLIER) as i32;
-const TILE_START_X: f32 = 12.0 * WINDOW_SIZE_MULTIPLIER;
-const TILE_START_Y: f32 = 55.0 * WINDOW_SIZE_MULTIPLIER;
-const TILE_SIZE: f32 = 16.0 * WINDOW_SIZE_MULTIPLIER;
-const SMILEY_START_X: f32 = 239.0 * WINDOW_SIZE_MULTIPLIER;
-const SMILEY_START_Y: f32 = 15.0 * WINDOW_SIZE_MULTIPLIER;
-const SMILEY_SIZE: f32 = 26.0 * WINDOW_SIZE_MULTIPLIER;
-const COUNTER_DIGIT_WIDTH: f32 = 13.0 * WINDOW_SIZE_MULTIPLIER;
-const COUNTER_DIGIT_HEIGHT: f32 = 23.0 * WINDOW_SIZE_MULTIPLIER;
-const MINES_COUNTER_START_X: f32 = 16.0 * WINDOW_SIZE_MULTIPLIER;
-const MINES_COUNTER_START_Y: f32 = 17.0 * WINDOW_SIZE_MULTIPLIER;
-const TIME_COUNTER_START_X: f32 = 446.0 * WINDOW_SIZE_MULTIPLIER;
-
-fn window_conf() -> Conf {
+use menu::{Transition, MENU_HEIGHT, MENU_WIDTH};
+use palette::Palette;
+use puzzle::{Pack, Puzzles};
+use race::Rival;
+use replay::{Playback, Replay};
+use settings::{ChordStyle, Difficulty, Fog, Mode, Settings, Solver, Variant, WrongFlags};
+use stats::Stats;
+use theme::{Colours, Textures, Theme, ThemeSelection};
+use trainer::Trainer;
+use tutorial::Tutorial;
+use versus::Versus;
+
+mod bitboard;
+mod config;
+mod coop;
+mod daily;
+mod grid;
+mod menu;
+mod palette;
+mod protocol;
+mod puzzle;
+mod race;
+mod replay;
+mod server;
+mod settings;
+mod stats;
+mod theme;
+mod trainer;
+mod tui;
+mod tutorial;
+mod versus;
+
+// frame measurements in unscaled pixels, taken from the classic expert layout
+const TILE_SIZE: f32 = 16.0;
+const SMILEY_SIZE: f32 = 26.0;
+const COUNTER_DIGIT_WIDTH: f32 = 13.0;
+const COUNTER_DIGIT_HEIGHT: f32 = 23.0;
+const OUTER_BEVEL: f32 = 3.0;
+const PANEL_MARGIN: f32 = 9.0;
+const HEADER_HEIGHT: f32 = 37.0;
+const HEADER_BEVEL: f32 = 2.0;
+const BOARD_BEVEL: f32 = 3.0;
+const BOARD_START_Y: f32 = 52.0;
+const COUNTER_START_X: f32 = 16.0;
+const COUNTER_START_Y: f32 = 17.0;
+const SMILEY_START_Y: f32 = 15.0;
+
+// copies of the opposite edges around a wrapping board are shaded so they read as not clickable
+const GHOST_SHADE: Color = Color::new(0.0, 0.0, 0.0, 0.3);
+
+// tiles opened by a flood fill pop open in a ripple out from the click
+const RIPPLE_STEP_SECONDS: f32 = 0.02;
+const RIPPLE_SECONDS: f32 = 0.12;
+
+// numbers in the fog can be read around the tiles opened by this many of the latest reveals, or
+// within this many tiles of the mouse, and take a moment to fade in and out
+const FOG_RECENT_REVEALS: usize = 5;
+const FOG_RADIUS: f32 = 2.5;
+const FOG_FADE_SECONDS: f32 = 0.4;
+
+fn window_conf(width: f32, height: f32) -> Conf {
     Conf {
         window_title: "Minesweeper".to_owned(),
         fullscreen: false,
-        window_width: WINDOW_WIDTH,
-        window_height: WINDOW_HEIGHT,
+        window_width: width as i32,
+        window_height: height as i32,
         window_resizable: false,
         ..Default::default()
     }
 }
 
+/// Screen positions of everything on the board, in scaled pixels.
+#[derive(Clone)]
+struct Layout {
+    scale: f32,
+    window_width: f32,
+    window_height: f32,
+    /// Left edge of the window this board is drawn in, past the first board when racing.
+    origin_x: f32,
+    topology: Topology,
+    /// Whether the board well has a ring of ghost tiles around the real ones.
+    ghost_edges: bool,
+    board_start_x: f32,
+    board_start_y: f32,
+    tile_start_x: f32,
+    tile_start_y: f32,
+    tile_size: f32,
+    board_width: f32,
+    board_height: f32,
+    panel_width: f32,
+    smiley_start_x: f32,
+    smiley_start_y: f32,
+    smiley_size: f32,
+    mines_counter_start_x: f32,
+    time_counter_start_x: f32,
+    counter_start_y: f32,
+    counter_digit_width: f32,
+    counter_digit_height: f32,
+}
+
+impl Layout {
+    fn new(width: usize, height: usize, scale: f32, topology: Topology, ghost_edges: bool) -> Self {
+        let ghost_tiles = if ghost_edges { 1 } else { 0 };
+        let ghost_width = ghost_tiles as f32 * TILE_SIZE;
+        let (board_width, board_height) = match topology {
+            Topology::Flat | Topology::Torus => (
+                (width + 2 * ghost_tiles) as f32 * TILE_SIZE,
+                (height + 2 * ghost_tiles) as f32 * TILE_SIZE,
+            ),
+            // odd rows stick out half a tile, and rows overlap by half a corner
+            Topology::Hex => (
+                (width as f32 + if height > 1 { 0.5 } else { 0.0 }) * TILE_SIZE,
+                (1.5 * (height - 1) as f32 + 2.0) * hex_radius(TILE_SIZE),
+            ),
+            // triangles are two tiles along each side, and overlap their neighbours by half
+            Topology::Triangle => (
+                (width + 1) as f32 * TILE_SIZE,
+                height as f32 * triangle_height(TILE_SIZE),
+            ),
+        };
+        // the header and the board well share a width, with one extra pixel of border on the right
+        let panel_width = board_width + 2.0 * BOARD_BEVEL;
+        let window_width = panel_width + 2.0 * PANEL_MARGIN + 1.0;
+        let window_height = BOARD_START_Y + board_height + 2.0 * BOARD_BEVEL + PANEL_MARGIN + 1.0;
+        let counter_width = 3.0 * COUNTER_DIGIT_WIDTH;
+
+        Self {
+            scale,
+            window_width: window_width * scale,
+            window_height: window_height * scale,
+            origin_x: 0.0,
+            topology,
+            ghost_edges,
+            board_start_x: (PANEL_MARGIN + BOARD_BEVEL) * scale,
+            board_start_y: (BOARD_START_Y + BOARD_BEVEL) * scale,
+            tile_start_x: (PANEL_MARGIN + BOARD_BEVEL + ghost_width) * scale,
+            tile_start_y: (BOARD_START_Y + BOARD_BEVEL + ghost_width) * scale,
+            tile_size: TILE_SIZE * scale,
+            board_width: board_width * scale,
+            board_height: board_height * scale,
+            panel_width: panel_width * scale,
+            smiley_start_x: ((window_width - SMILEY_SIZE) / 2.0).floor() * scale,
+            smiley_start_y: SMILEY_START_Y * scale,
+            smiley_size: SMILEY_SIZE * scale,
+            mines_counter_start_x: COUNTER_START_X * scale,
+            time_counter_start_x: (panel_width + 2.0 * PANEL_MARGIN
+                - COUNTER_START_X
+                - counter_width)
+                * scale,
+            counter_start_y: COUNTER_START_Y * scale,
+            counter_digit_width: COUNTER_DIGIT_WIDTH * scale,
+            counter_digit_height: COUNTER_DIGIT_HEIGHT * scale,
+        }
+    }
+
+    /// The same board drawn again to the right of this one.
+    fn beside(&self) -> Self {
+        let shift = self.window_width;
+
+        Self {
+            origin_x: self.origin_x + shift,
+            board_start_x: self.board_start_x + shift,
+            tile_start_x: self.tile_start_x + shift,
+            smiley_start_x: self.smiley_start_x + shift,
+            mines_counter_start_x: self.mines_counter_start_x + shift,
+            time_counter_start_x: self.time_counter_start_x + shift,
+            ..self.clone()
+        }
+    }
+
+    /// The middle of a tile.
+    fn tile_centre(&self, col: usize, row: usize) -> (f32, f32) {
+        match self.topology {
+            Topology::Flat | Topology::Torus => (
+                self.tile_start_x + (col as f32 + 0.5) * self.tile_size,
+                self.tile_start_y + (row as f32 + 0.5) * self.tile_size,
+            ),
+            Topology::Hex => {
+                let radius = hex_radius(self.tile_size);
+
+                (
+                    self.tile_start_x
+                        + (col as f32 + 0.5 + (row % 2) as f32 / 2.0) * self.tile_size,
+                    self.tile_start_y + radius + row as f32 * 1.5 * radius,
+                )
+            }
+            // the middle is a third of the way up from the base
+            Topology::Triangle => {
+                let fraction = if pointing_up(col, row) { 2.0 } else { 1.0 } / 3.0;
+
+                (
+                    self.tile_start_x + (col + 1) as f32 * self.tile_size,
+                    self.tile_start_y + (row as f32 + fraction) * triangle_height(self.tile_size),
+                )
+            }
+        }
+    }
+
+    /// The corners of a tile going clockwise, and how many of them there are.
+    fn tile_corners(&self, col: usize, row: usize) -> ([Vec2; 6], usize) {
+        let (x, y) = self.tile_centre(col, row);
+        let mut corners = [Vec2::ZERO; 6];
+
+        match self.topology {
+            Topology::Flat | Topology::Torus => {
+                let half = self.tile_size / 2.0;
+                corners[..4].copy_from_slice(&[
+                    vec2(x - half, y - half),
+                    vec2(x + half, y - half),
+                    vec2(x + half, y + half),
+                    vec2(x - half, y + half),
+                ]);
+                (corners, 4)
+            }
+            Topology::Hex => {
+                let radius = hex_radius(self.tile_size);
+
+                for (i, corner) in corners.iter_mut().enumerate() {
+                    let angle = (i as f32 * 60.0 - 90.0).to_radians();
+                    *corner = vec2(x + radius * angle.cos(), y + radius * angle.sin());
+                }
+                (corners, 6)
+            }
+            Topology::Triangle => {
+                let height = triangle_height(self.tile_size);
+                let (left, right) = (x - self.tile_size, x + self.tile_size);
+
+                corners[..3].copy_from_slice(&if pointing_up(col, row) {
+                    let top = y - 2.0 * height / 3.0;
+                    [
+                        vec2(x, top),
+                        vec2(right, top + height),
+                        vec2(left, top + height),
+                    ]
+                } else {
+                    let top = y - height / 3.0;
+                    [vec2(left, top), vec2(right, top), vec2(x, top + height)]
+                });
+                (corners, 3)
+            }
+        }
+    }
+
+    /// The top left of the square in the middle of a tile that its textures are drawn in.
+    fn tile_position(&self, col: usize, row: usize) -> (f32, f32) {
+        let (x, y) = self.tile_centre(col, row);
+        (x - self.tile_size / 2.0, y - self.tile_size / 2.0)
+    }
+
+    /// The tile nearest to a point. Points over the ghost edges give the nearest real tile,
+    /// which the point then isn't hovering.
+    fn tile_at(&self, x: f32, y: f32, width: usize, height: usize) -> (usize, usize) {
+        match self.topology {
+            Topology::Flat | Topology::Torus => (
+                (((x - self.tile_start_x) / self.tile_size) as usize).min(width - 1),
+                (((y - self.tile_start_y) / self.tile_size) as usize).min(height - 1),
+            ),
+            // hexes are exactly the points nearer their middle than any other, so the answer
+            // is whichever of the tiles around a rough guess has the nearest middle
+            Topology::Hex => {
+                let col = (((x - self.tile_start_x) / self.tile_size) as usize).min(width - 1);
+                let row = (((y - self.tile_start_y) / (1.5 * hex_radius(self.tile_size))) as usize)
+                    .min(height - 1);
+                let distance = |(col, row)| {
+                    let (centre_x, centre_y) = self.tile_centre(col, row);
+                    (x - centre_x).powi(2) + (y - centre_y).powi(2)
+                };
+
+                (row.saturating_sub(1)..=(row + 1).min(height - 1))
+                    .flat_map(|row| {
+                        (col.saturating_sub(1)..=(col + 1).min(width - 1))
+                            .map(move |col| (col, row))
+                    })
+                    .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+                    .unwrap_or((col, row))
+            }
+            // the point is in one of the two triangles overlapping the column it is over
+            Topology::Triangle => {
+                let col = (((x - self.tile_start_x) / self.tile_size) as usize).min(width - 1);
+                let row = (((y - self.tile_start_y) / triangle_height(self.tile_size)) as usize)
+                    .min(height - 1);
+
+                (col.saturating_sub(1)..=col)
+                    .map(|col| (col, row))
+                    .find(|&(col, row)| self.hovering_tile(x, y, col, row))
+                    .unwrap_or((col, row))
+            }
+        }
+    }
+
+    fn hovering_tile(&self, mouse_x: f32, mouse_y: f32, col: usize, row: usize) -> bool {
+        match self.topology {
+            Topology::Flat | Topology::Torus => {
+                let (tile_x, tile_y) = self.tile_position(col, row);
+                hovering_square(mouse_x, mouse_y, tile_x, tile_y, self.tile_size)
+            }
+            Topology::Hex => {
+                let (centre_x, centre_y) = self.tile_centre(col, row);
+                let (x, y) = ((mouse_x - centre_x).abs(), (mouse_y - centre_y).abs());
+
+                // inside the flat sides, and under the sloping ones
+                x <= self.tile_size / 2.0 && y <= hex_radius(self.tile_size) - x / 3.0_f32.sqrt()
+            }
+            Topology::Triangle => {
+                let height = triangle_height(self.tile_size);
+                // across from the left corner in tiles, and down from the top in heights
+                let across = (mouse_x - self.tile_start_x) / self.tile_size - col as f32;
+                let down = (mouse_y - self.tile_start_y) / height - row as f32;
+                // how far across from the middle the sides are at that height
+                let half_width = if pointing_up(col, row) {
+                    down
+                } else {
+                    1.0 - down
+                };
+
+                (0.0..=1.0).contains(&down) && (across - 1.0).abs() <= half_width
+            }
+        }
+    }
+
+    fn hovering_smiley(&self, mouse_x: f32, mouse_y: f32) -> bool {
+        hovering_square(
+            mouse_x,
+            mouse_y,
+            self.smiley_start_x,
+            self.smiley_start_y,
+            self.smiley_size,
+        )
+    }
+}
+
 #[derive(PartialEq, Debug, Clone, Copy)]
 enum State {
     Playing,
     Dead,
     Won,
     NewGame,
+    Menu,
+    Settings,
+    CustomGame,
+    Stats,
+    Leaderboards(Difficulty),
 }
 
-#[derive(PartialEq, Default, Clone, Copy)]
+impl State {
+    fn in_menu(self) -> bool {
+        matches!(
+            self,
+            State::Menu
+                | State::Settings
+                | State::CustomGame
+                | State::Stats
+                | State::Leaderboards(_)
+        )
+    }
+}
+
+#[derive(PartialEq, Debug, Default, Clone, Copy)]
 enum TileState {
     #[default]
     Hidden,
     Revealed,
     Flagged,
+    Question,
 }
 
 #[derive(Default, Clone)]
 struct Tile {
     state: TileState,
+    /// Mines in the tiles the clue covers.
     neighbour_mines_count: u8,
-    mine: bool,
+    /// The number shown once the tile is revealed, which lying clues make different from the
+    /// mines it covers.
+    clue: u8,
+    mines: u8,
+    /// Flags on the tile, there can be more than one when tiles hold more than one mine.
+    flags: u8,
+    /// How many reveals had been made when this tile was opened, counting its own.
+    revealed_on: usize,
+}
+
+/// A single move on the board, as made by a player, the bot or read back from a replay.
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Action {
+    Reveal(usize, usize),
+    /// Cycles a tile through flagged, question marked if they are turned on, and hidden.
+    Flag(usize, usize),
+    Chord(usize, usize),
+    /// One pass of the bot over the board, as asked for in assisted mode.
+    Solve,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Reveal(col, row) => write!(f, "reveal {col} {row}"),
+            Action::Flag(col, row) => write!(f, "flag {col} {row}"),
+            Action::Chord(col, row) => write!(f, "chord {col} {row}"),
+            Action::Solve => write!(f, "solve"),
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> eyre::Result<Self> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+
+        let tile = || -> eyre::Result<(usize, usize)> {
+            match words[1..] {
+                [col, row] => Ok((col.parse()?, row.parse()?)),
+                _ => bail!("expected a column and row after {:?}", words[0]),
+            }
+        };
+
+        Ok(match words.first() {
+            Some(&"reveal") => {
+                let (col, row) = tile()?;
+                Action::Reveal(col, row)
+            }
+            Some(&"flag") => {
+                let (col, row) = tile()?;
+                Action::Flag(col, row)
+            }
+            Some(&"chord") => {
+                let (col, row) = tile()?;
+                Action::Chord(col, row)
+            }
+            Some(&"solve") if words.len() == 1 => Action::Solve,
+            _ => bail!("unknown action {s:?}"),
+        })
+    }
+}
+
+/// A tile opened by a move, and how many steps out from the tile the move was on it is.
+#[derive(Clone, Copy)]
+struct Opened {
+    col: usize,
+    row: usize,
+    distance: usize,
 }
 
-struct Textures {
-    background: Texture2D,
-    tile: Texture2D,
-    flag: Texture2D,
-    mine: Texture2D,
-    cross: Texture2D,
-    smiley: Texture2D,
-    smiley_open: Texture2D,
-    smiley_dead: Texture2D,
-    smiley_clicked: Texture2D,
-    smiley_glasses: Texture2D,
-    neighbour_mines: [Texture2D; 8],
-    counter_digits: [Texture2D; 10],
+/// What the numbers in sight through the fog depend on.
+#[derive(PartialEq, Clone, Copy)]
+enum Sight {
+    Clear,
+    /// The reveals made so far, for [`Fog::Recent`].
+    Reveals(usize),
+    /// The tile under the mouse, if there is one, for [`Fog::Cursor`].
+    Mouse(Option<(usize, usize)>),
 }
 
 struct Board {
-    tiles: Vec<Vec<Tile>>,
+    tiles: Grid<Tile>,
+    topology: Topology,
+    mines_per_tile: u8,
+    clues: Clues,
+    mines: usize,
+    /// Tiles without a mine. Less than the tiles less the mines when tiles can hold several.
+    safe_tiles: usize,
+    /// Seeds the mine placement, so the same seed and first click always make the same board.
+    seed: u64,
+    /// Whether the mines were loaded from a file rather than placed on the first click.
+    fixed_layout: bool,
+    /// The day this is the daily board of, which can only be opened from its start tile.
+    daily: Option<u64>,
+    /// Tiles a puzzle starts with open. Puzzles have to be solved without a mistake, so a flag
+    /// on a safe tile loses them.
+    given: Option<Grid<bool>>,
+    /// Flags on the board, counting every flag on tiles with more than one.
     number_flagged: usize,
+    /// Safe tiles revealed so far, the game is won once every one of them is.
+    revealed_safe: usize,
+    /// Revealed tiles whose surroundings changed since the bot last looked at them.
+    solver_work: Vec<(usize, usize)>,
+    queued: Grid<bool>,
     state: State,
     start: Instant,
     elapsed: usize,
     unflagged_mines: Vec<(usize, usize)>,
+    /// Every move made so far, with the seconds since the first click it was made at.
+    history: Vec<(f32, Action)>,
+    wrong_flags: WrongFlags,
+    wrong_flags_cleared: usize,
+    /// Tiles opened recently enough that they are still animating, with when they were opened.
+    opening: Vec<(Opened, Instant)>,
+    /// Reveals made so far, each one counting once however many tiles it opened.
+    reveals: usize,
+    /// Whether the number on each tile can be read through the fog. The bot only uses these.
+    in_sight: Grid<bool>,
+    /// What `in_sight` was last worked out from, so that it is only worked out again once that
+    /// changes.
+    looked: Option<Sight>,
+    /// How readable each number is drawn, easing towards `in_sight` between 0 and 1.
+    visibility: Grid<f32>,
 }
 
 impl Board {
-    fn new(width: usize, height: usize) -> Self {
+    fn new(width: usize, height: usize, mines: usize, seed: u64, variant: Variant) -> Self {
         Self {
-            tiles: vec![vec![Tile::default(); height]; width],
+            tiles: Grid::new(width, height, Tile::default()),
+            topology: variant.topology,
+            mines_per_tile: variant.mines_per_tile,
+            clues: variant.clues,
+            mines,
+            safe_tiles: (width * height).saturating_sub(mines),
+            seed,
+            fixed_layout: false,
+            daily: None,
+            given: None,
             number_flagged: 0,
+            revealed_safe: 0,
+            solver_work: vec![],
+            queued: Grid::new(width, height, false),
             state: State::NewGame,
             start: Instant::now(),
             elapsed: 0,
             unflagged_mines: vec![],
+            history: vec![],
+            wrong_flags: WrongFlags::default(),
+            wrong_flags_cleared: 0,
+            opening: vec![],
+            reveals: 0,
+            in_sight: Grid::new(width, height, true),
+            looked: None,
+            visibility: Grid::new(width, height, 1.0),
         }
     }
 
-    fn update(&mut self, mouse_x: f32, mouse_y: f32) {
-        if self.state == State::NewGame {
-            let (col, row) = (
-                (((mouse_x - TILE_START_X) / TILE_SIZE) as usize).min(EXPERT_WIDTH - 1),
-                (((mouse_y - TILE_START_Y) / TILE_SIZE) as usize).min(EXPERT_HEIGHT - 1),
-            );
+    /// A board with the mines already placed. The first click is not guaranteed to be safe.
+    /// Tiles holding more mines than the variant allows raise its limit to match, and lying
+    /// clues lie the same way every time the layout is played.
+    fn with_layout(layout: &Grid<u8>, variant: Variant) -> Self {
+        let mut tiles = layout.map(|&mines| Tile {
+            mines,
+            ..Tile::default()
+        });
+        count_neighbour_mines(&mut tiles, variant, &mut fastrand::Rng::with_seed(0));
+
+        let variant = Variant {
+            mines_per_tile: layout.iter().copied().fold(variant.mines_per_tile, u8::max),
+            ..variant
+        };
 
-            if is_mouse_button_pressed(MouseButton::Left)
-                && hovering_tile(mouse_x, mouse_y, col, row)
-            {
-                self.start(col, row);
+        Self {
+            mines: layout.iter().map(|&mines| mines as usize).sum(),
+            safe_tiles: layout.iter().filter(|&&mines| mines == 0).count(),
+            tiles,
+            fixed_layout: true,
+            ..Self::new(layout.width(), layout.height(), 0, 0, variant)
+        }
+    }
+
+    /// A fresh board of the same size. Loaded layouts and the daily board are kept, anything
+    /// else gets new mines.
+    fn restart(&self) -> Self {
+        if self.daily.is_some() {
+            self.reseeded(self.seed)
+        } else {
+            self.reseeded(fastrand::u64(..))
+        }
+    }
+
+    /// A fresh board of the same size with its mines placed from `seed`, unless they were
+    /// loaded from a file.
+    fn reseeded(&self, seed: u64) -> Self {
+        if self.fixed_layout {
+            let mut board = Self::with_layout(&self.mine_layout(), self.variant());
+
+            if let Some(given) = &self.given {
+                board.give(given.clone());
+            }
+
+            board
+        } else {
+            Self {
+                daily: self.daily,
+                ..Self::new(
+                    self.width(),
+                    self.height(),
+                    self.mines,
+                    seed,
+                    self.variant(),
+                )
             }
         }
+    }
 
-        for col in 0..self.tiles.len() {
-            for row in 0..self.tiles[0].len() {
-                if self.neighbour_mines(col, row) == 0
-                    && !self.mine(col, row)
-                    && self.revealed(col, row)
-                {
-                    self.reveal_empty_space_at(col, row)
-                }
+    /// Opens the tiles a puzzle starts with and starts the clock, without a first click.
+    fn give(&mut self, given: Grid<bool>) {
+        self.state = State::Playing;
+        self.start = Instant::now();
+
+        for tile in given.positions() {
+            if given[tile] {
+                self.reveal(tile.0, tile.1);
             }
         }
 
-        if self.state == State::Playing {
-            // self.player_game(mouse_x, mouse_y);
-            self.computer_game();
+        self.given = Some(given);
+    }
+
+    fn variant(&self) -> Variant {
+        Variant {
+            topology: self.topology,
+            mines_per_tile: self.mines_per_tile,
+            clues: self.clues,
         }
     }
 
-    fn player_game(&mut self, mouse_x: f32, mouse_y: f32) {
-        let (col, row) = (
-            (((mouse_x - TILE_START_X) / TILE_SIZE) as usize).min(EXPERT_WIDTH - 1),
-            (((mouse_y - TILE_START_Y) / TILE_SIZE) as usize).min(EXPERT_HEIGHT - 1),
-        );
+    /// How many mines each tile holds.
+    fn mine_layout(&self) -> Grid<u8> {
+        self.tiles.map(|tile| tile.mines)
+    }
+
+    fn update(&mut self, layout: &Layout, settings: &Settings, mouse_x: f32, mouse_y: f32) {
+        self.wrong_flags = settings.wrong_flags;
+
+        if let Some(action) = self.input_action(layout, settings, mouse_x, mouse_y) {
+            self.apply(action, settings);
+        }
+
+        if settings.mode == Mode::Bot && self.state == State::Playing {
+            self.look(settings.fog, layout, mouse_x, mouse_y);
+            self.computer_game(settings.solver);
+        }
+    }
+
+    /// Works out which numbers can be read through the fog. Everything can once the game is
+    /// over.
+    fn look(&mut self, fog: Fog, layout: &Layout, mouse_x: f32, mouse_y: f32) {
+        let looked = match fog {
+            _ if self.state != State::Playing => Sight::Clear,
+            Fog::Off => Sight::Clear,
+            Fog::Recent => Sight::Reveals(self.reveals),
+            Fog::Cursor => {
+                let (col, row) = layout.tile_at(mouse_x, mouse_y, self.width(), self.height());
+                let hovering = layout.hovering_tile(mouse_x, mouse_y, col, row);
+                Sight::Mouse(hovering.then_some((col, row)))
+            }
+        };
+
+        if self.looked == Some(looked) {
+            return;
+        }
+        self.looked = Some(looked);
+
+        for (col, row) in self.tiles.positions() {
+            self.in_sight[(col, row)] = match looked {
+                Sight::Clear => true,
+                // near enough includes the numbers bordering what the reveals opened
+                Sight::Reveals(reveals) => std::iter::once((col, row))
+                    .chain(self.tiles.neighbours(col, row, self.topology))
+                    .any(|(col, row)| {
+                        self.revealed(col, row)
+                            && self.tiles[(col, row)].revealed_on + FOG_RECENT_REVEALS > reveals
+                    }),
+                // the fog stays put while the mouse is on one tile, so it is measured from there
+                Sight::Mouse(mouse) => mouse.is_some_and(|(mouse_col, mouse_row)| {
+                    Vec2::from(layout.tile_centre(col, row))
+                        .distance(layout.tile_centre(mouse_col, mouse_row).into())
+                        <= FOG_RADIUS * layout.tile_size
+                }),
+            };
+        }
+    }
 
-        if self.state == State::Playing
-            && (is_mouse_button_released(MouseButton::Left)
-                && is_mouse_button_released(MouseButton::Right)
-                || is_mouse_button_down(MouseButton::Left)
+    /// Eases how readable each number is drawn towards whether it is in sight.
+    fn fade(&mut self, seconds: f32) {
+        let step = seconds / FOG_FADE_SECONDS;
+
+        for (col, row) in self.tiles.positions() {
+            let visibility = self.visibility[(col, row)];
+
+            // tiles opened while out of sight never show their number
+            self.visibility[(col, row)] = match self.in_sight[(col, row)] {
+                _ if !self.revealed(col, row) => f32::from(self.in_sight[(col, row)]),
+                true => (visibility + step).min(1.0),
+                false => (visibility - step).max(0.0),
+            };
+        }
+    }
+
+    /// Works out what the mouse and keyboard asked for this frame, if anything.
+    fn input_action(
+        &self,
+        layout: &Layout,
+        settings: &Settings,
+        mouse_x: f32,
+        mouse_y: f32,
+    ) -> Option<Action> {
+        let (col, row) = layout.tile_at(mouse_x, mouse_y, self.width(), self.height());
+        let hovering = layout.hovering_tile(mouse_x, mouse_y, col, row);
+
+        match self.state {
+            State::NewGame => (is_mouse_button_pressed(MouseButton::Left) && hovering)
+                .then_some(Action::Reveal(col, row)),
+            State::Playing if settings.mode != Mode::Bot => {
+                if settings.mode == Mode::Assisted && is_key_pressed(KeyCode::Space) {
+                    return Some(Action::Solve);
+                }
+
+                let classic_chord = is_mouse_button_released(MouseButton::Left)
                     && is_mouse_button_released(MouseButton::Right)
-                || is_mouse_button_down(MouseButton::Right)
-                    && is_mouse_button_released(MouseButton::Left)
-                || is_mouse_button_released(MouseButton::Middle))
-            && self.revealed(col, row)
-            && surrounding_tiles(col, row)
-                .into_iter()
-                .filter(|(col, row)| self.mine(*col, *row))
-                .count()
-                == surrounding_tiles(col, row)
-                    .into_iter()
-                    .filter(|(col, row)| self.flagged(*col, *row))
-                    .count()
-        {
-            let surrounding_tiles = surrounding_tiles(col, row);
-
-            for (surrounding_tile_col, surrounding_tile_row) in surrounding_tiles {
-                if self.mine(surrounding_tile_col, surrounding_tile_row) {
-                    if !self.flagged(surrounding_tile_col, surrounding_tile_row) {
-                        self.state = State::Dead;
-                        self.reveal_all_mines();
-                        self.unflagged_mines
-                            .push((surrounding_tile_col, surrounding_tile_row))
-                    } else {
-                        continue;
+                    || is_mouse_button_down(MouseButton::Left)
+                        && is_mouse_button_released(MouseButton::Right)
+                    || is_mouse_button_down(MouseButton::Right)
+                        && is_mouse_button_released(MouseButton::Left)
+                    || is_mouse_button_released(MouseButton::Middle);
+                let chord = match settings.chord_style {
+                    ChordStyle::Classic => classic_chord,
+                    ChordStyle::LeftClick => {
+                        classic_chord || is_mouse_button_released(MouseButton::Left)
                     }
+                    ChordStyle::Disabled => false,
+                };
+
+                if chord && self.revealed(col, row) && self.satisfied(col, row) {
+                    Some(Action::Chord(col, row))
+                } else if !self.revealed(col, row)
+                    && is_mouse_button_pressed(MouseButton::Right)
+                    && hovering
+                {
+                    Some(Action::Flag(col, row))
+                } else if is_mouse_button_pressed(MouseButton::Left)
+                    && hovering
+                    && !self.flagged(col, row)
+                {
+                    Some(Action::Reveal(col, row))
+                } else {
+                    None
                 }
+            }
+            _ => None,
+        }
+    }
 
-                if self.state == State::Playing {
-                    self.tiles[surrounding_tile_col][surrounding_tile_row].state =
-                        TileState::Revealed;
-                }
+    /// Makes a move and adds it to the history. Moves that make no sense in the current state
+    /// of the board, such as revealing a flagged tile, are ignored.
+    fn apply(&mut self, action: Action, settings: &Settings) {
+        self.wrong_flags = settings.wrong_flags;
+
+        let seconds = match self.state {
+            State::NewGame => 0.0,
+            State::Playing => self.start.elapsed().as_secs_f32(),
+            _ => return,
+        };
+
+        match action {
+            // the daily board is only sure to need no guesses from the tile it was checked from
+            Action::Reveal(col, row)
+                if self.state == State::NewGame
+                    && self.daily.is_some()
+                    && (col, row) != daily::start_tile() =>
+            {
+                return
             }
-        } else if self.state == State::Playing
-            && !self.revealed(col, row)
-            && is_mouse_button_pressed(MouseButton::Right)
-            && hovering_tile(mouse_x, mouse_y, col, row)
-        {
-            if self.flagged(col, row) {
-                self.number_flagged -= 1;
-                self.tiles[col][row].state = TileState::Hidden
-            } else {
-                self.number_flagged += 1;
-                self.tiles[col][row].state = TileState::Flagged
+            Action::Reveal(col, row) if self.state == State::NewGame => self.start(col, row),
+            Action::Reveal(col, row) if !self.flagged(col, row) => {
+                self.reveal_or_explode(col, row);
+            }
+            Action::Flag(col, row) if self.state == State::Playing && !self.revealed(col, row) => {
+                let tile = &mut self.tiles[(col, row)];
+
+                // flags stack up to as many mines as a tile can hold before coming off together
+                tile.state = match tile.state {
+                    TileState::Flagged if tile.flags < self.mines_per_tile => {
+                        tile.flags += 1;
+                        self.number_flagged += 1;
+                        TileState::Flagged
+                    }
+                    TileState::Flagged => {
+                        self.number_flagged -= tile.flags as usize;
+                        tile.flags = 0;
+
+                        if settings.question_marks {
+                            TileState::Question
+                        } else {
+                            TileState::Hidden
+                        }
+                    }
+                    TileState::Question => TileState::Hidden,
+                    _ => {
+                        tile.flags = 1;
+                        self.number_flagged += 1;
+                        TileState::Flagged
+                    }
+                };
+                self.queue_solver_work(col, row);
+
+                if self.given.is_some() && self.flagged(col, row) && !self.mine(col, row) {
+                    self.state = State::Dead;
+                    self.reveal_all_mines();
+                }
             }
-        } else if (self.state == State::Playing || self.state == State::NewGame)
-            && is_mouse_button_pressed(MouseButton::Left)
-            && hovering_tile(mouse_x, mouse_y, col, row)
-            && !self.flagged(col, row)
-        {
-            self.tiles[col][row].state = TileState::Revealed;
+            Action::Chord(col, row)
+                if self.state == State::Playing
+                    && self.revealed(col, row)
+                    && self.satisfied(col, row) =>
+            {
+                for (surrounding_tile_col, surrounding_tile_row) in self.surrounding_tiles(col, row)
+                {
+                    if self.flagged(surrounding_tile_col, surrounding_tile_row) {
+                        continue;
+                    }
 
-            if self.mine(col, row) {
-                self.state = State::Dead;
-                self.reveal_all_mines();
-                self.unflagged_mines.push((col, row));
+                    if self.mine(surrounding_tile_col, surrounding_tile_row) {
+                        self.explode(surrounding_tile_col, surrounding_tile_row);
+                    } else if self.state == State::Playing {
+                        self.reveal_or_explode(surrounding_tile_col, surrounding_tile_row);
+                    }
+                }
             }
+            Action::Solve if self.state == State::Playing => self.computer_game(settings.solver),
+            _ => return,
         }
+
+        self.history.push((seconds, action));
     }
 
-    fn computer_game(&mut self) {
-        for col in 0..self.tiles.len() {
-            for row in 0..self.tiles[0].len() {
-                // only allowed to use revealed tiles
-                if !self.revealed(col, row) {
+    /// One pass of the bot over the tiles that changed since its last pass. Anything this pass
+    /// changes is left for the next one, so the bot can be watched working through the board.
+    fn computer_game(&mut self, solver: Solver) {
+        let work = std::mem::take(&mut self.solver_work);
+
+        for &tile in &work {
+            self.queued[tile] = false;
+        }
+
+        for (col, row) in work {
+            if self.state != State::Playing {
+                return;
+            }
+
+            // numbers in the fog are kept for when they come back into sight
+            if !self.in_sight[(col, row)] {
+                self.queued[(col, row)] = true;
+                self.solver_work.push((col, row));
+                continue;
+            }
+
+            // room for more mines under the tiles around, after the flags already on them
+            let flags = self.neighbour_flags(col, row);
+            let neighbour_room: u8 = self
+                .surrounding_tiles(col, row)
+                .filter(|(col, row)| !self.revealed(*col, *row))
+                .map(|tile| self.room(tile))
+                .sum();
+
+            // the mines the clue could stand for that fit between the flags and the room left
+            let possible = self
+                .clues
+                .mine_counts(self.clue(col, row))
+                .filter(|&mines| mines >= flags && mines <= flags + neighbour_room);
+            let (Some(fewest), Some(most)) = (possible.clone().min(), possible.max()) else {
+                continue;
+            };
+
+            // trivial corner 1s etc, a tile holds at least the mines that don't fit anywhere else
+            let unflagged_mines = fewest - flags;
+            for (col, row) in self.surrounding_tiles(col, row) {
+                if self.revealed(col, row) {
                     continue;
                 }
 
-                let surrounding_tiles = surrounding_tiles(col, row);
-                let neighbour_mines = self.neighbour_mines(col, row);
-                let neighbour_flagged = surrounding_tiles
-                    .iter()
-                    .filter(|(col, row)| self.flagged(*col, *row))
-                    .count() as u8;
-                // let effective_neighbour_mines = neighbour_mines - neighbour_flagged;
-                let neighbour_unrevealed = surrounding_tiles
-                    .iter()
-                    .filter(|(col, row)| !self.revealed(*col, *row))
-                    .count() as u8;
-
-                // trivial corner 1s etc
-                if self.revealed(col, row) && neighbour_mines == neighbour_unrevealed {
-                    for (col, row) in surrounding_tiles.iter() {
-                        if !self.revealed(*col, *row) {
-                            self.tiles[*col][*row].state = TileState::Flagged;
-                        }
-                    }
+                let room_elsewhere = neighbour_room - self.room((col, row));
+                if unflagged_mines > room_elsewhere {
+                    let flags = self.tiles[(col, row)].flags + unflagged_mines - room_elsewhere;
+                    self.set_flagged(col, row, flags);
                 }
+            }
 
-                if self.satisfied(col, row) {
-                    for (col, row) in surrounding_tiles.iter() {
-                        if !self.flagged(*col, *row) {
-                            self.tiles[*col][*row].state = TileState::Revealed;
-                        }
+            if most == flags {
+                for (col, row) in self.surrounding_tiles(col, row) {
+                    if !self.flagged(col, row) {
+                        self.reveal_or_explode(col, row);
                     }
                 }
+            }
 
+            // the patterns assume square tiles holding one mine each, with honest numbers
+            // counting the tiles around
+            if solver == Solver::Patterns
+                && self.topology.square()
+                && self.mines_per_tile == 1
+                && self.clues == Clues::Classic
+            {
                 self.solve_121s(col, row);
             }
         }
     }
 
+    /// Looks for a 1-2-1 along a row or column with three hidden tiles on one side of it. The
+    /// tiles beside the 1s on that side are mines and the one beside the 2 is safe.
     fn solve_121s(&mut self, col: usize, row: usize) {
         if self.effective_neighbour_mines(col, row) != 2 {
             return;
         }
 
-        // has tiles to left and right
-        if col + 1 < EXPERT_WIDTH && col != 0 {
+        // along a row with the tiles below then above, then down a column with the tiles to the
+        // left then right
+        for (along, sides) in [((1, 0), [(0, 1), (0, -1)]), ((0, 1), [(-1, 0), (1, 0)])] {
+            let (Some(before), Some(after)) = (
+                self.offset((col, row), (-along.0, -along.1)),
+                self.offset((col, row), along),
+            ) else {
+                continue;
+            };
+
             // 121 found
-            if self.effective_neighbour_mines(col + 1, row) == 1
-                && self.revealed(col + 1, row)
-                && self.effective_neighbour_mines(col - 1, row) == 1
-                && self.revealed(col - 1, row)
+            if !(self.effective_neighbour_mines(before.0, before.1) == 1
+                && self.revealed(before.0, before.1)
+                && self.in_sight[before]
+                && self.effective_neighbour_mines(after.0, after.1) == 1
+                && self.revealed(after.0, after.1)
+                && self.in_sight[after])
             {
-                // has tiles below
-                if row + 1 < EXPERT_HEIGHT {
-                    // all 3 tiles below arent revealed
-                    if !self.revealed(col, row + 1)
-                        && !self.revealed(col + 1, row + 1)
-                        && !self.revealed(col - 1, row + 1)
-                    {
-                        self.tiles[col - 1][row + 1].state = TileState::Flagged;
-                        self.tiles[col + 1][row + 1].state = TileState::Flagged;
-                        self.tiles[col][row + 1].state = TileState::Revealed;
-                        println!("DOWN 121 solved {col} {row}");
-                    }
-                }
-                // has tiles above
-                if row != 0 {
-                    // all 3 tiles above arent revealed
-                    if !self.revealed(col, row - 1)
-                        && !self.revealed(col + 1, row - 1)
-                        && !self.revealed(col - 1, row - 1)
-                    {
-                        self.tiles[col - 1][row - 1].state = TileState::Flagged;
-                        self.tiles[col + 1][row - 1].state = TileState::Flagged;
-                        self.tiles[col][row - 1].state = TileState::Revealed;
-                        println!("UP 121 solved {col} {row}");
-                    }
-                }
+                continue;
             }
-        }
-        // has tiles above and below
-        if row + 1 < EXPERT_HEIGHT && row != 0 {
-            // 121 found
-            if self.effective_neighbour_mines(col, row + 1) == 1
-                && self.revealed(col, row + 1)
-                && self.effective_neighbour_mines(col, row - 1) == 1
-                && self.revealed(col, row - 1)
-            {
-                // has tiles to left
-                if col != 0 {
-                    // all 3 tiles to left arent revealed
-                    if !self.revealed(col - 1, row)
-                        && !self.revealed(col - 1, row + 1)
-                        && !self.revealed(col - 1, row - 1)
-                    {
-                        self.tiles[col - 1][row + 1].state = TileState::Flagged;
-                        self.tiles[col - 1][row - 1].state = TileState::Flagged;
-                        self.tiles[col - 1][row].state = TileState::Revealed;
-                        println!("LEFT 121 solved {col} {row}");
-                    }
-                    // has tiles to right
-                }
-                if col + 1 < EXPERT_WIDTH {
-                    // all 3 tiles to right arent revealed
-                    if !self.revealed(col + 1, row)
-                        && !self.revealed(col + 1, row + 1)
-                        && !self.revealed(col + 1, row - 1)
-                    {
-                        self.tiles[col + 1][row + 1].state = TileState::Flagged;
-                        self.tiles[col + 1][row - 1].state = TileState::Flagged;
-                        self.tiles[col + 1][row].state = TileState::Revealed;
-                        println!("RIGHT 121 solved {col} {row}");
-                    }
+
+            for side in sides {
+                let (Some(beside_before), Some(beside), Some(beside_after)) = (
+                    self.offset(before, side),
+                    self.offset((col, row), side),
+                    self.offset(after, side),
+                ) else {
+                    continue;
+                };
+
+                // all 3 tiles on this side arent revealed
+                if !self.revealed(beside_before.0, beside_before.1)
+                    && !self.revealed(beside.0, beside.1)
+                    && !self.revealed(beside_after.0, beside_after.1)
+                {
+                    self.set_flagged(beside_before.0, beside_before.1, 1);
+                    self.set_flagged(beside_after.0, beside_after.1, 1);
+                    self.reveal_or_explode(beside.0, beside.1);
                 }
             }
         }
     }
 
+    /// What the clue on a tile says is left unflagged, or none if there are too many flags.
     fn effective_neighbour_mines(&self, col: usize, row: usize) -> u8 {
-        let neighbour_mines = self.neighbour_mines(col, row);
-        let neighbour_flagged = surrounding_tiles(col, row)
-            .iter()
-            .filter(|(col, row)| self.flagged(*col, *row))
-            .count() as u8;
-
-        neighbour_mines - neighbour_flagged
+        self.clue(col, row)
+            .saturating_sub(self.neighbour_flags(col, row))
     }
 
+    /// Whether the clue on a tile shows as many mines as there are flags around it.
     fn satisfied(&self, col: usize, row: usize) -> bool {
-        self.neighbour_mines(col, row)
-            == surrounding_tiles(col, row)
-                .into_iter()
-                .filter(|(col, row)| self.flagged(*col, *row))
-                .count() as u8
+        self.clue(col, row) == self.neighbour_flags(col, row)
+    }
+
+    fn neighbour_flags(&self, col: usize, row: usize) -> u8 {
+        self.surrounding_tiles(col, row)
+            .map(|tile| self.tiles[tile].flags)
+            .sum()
     }
 
     fn start(&mut self, start_col: usize, start_row: usize) {
-        self.tiles = generate_fair_game(start_col, start_row);
+        if !self.fixed_layout {
+            self.tiles = generate_fair_game(
+                start_col,
+                start_row,
+                self.width(),
+                self.height(),
+                self.mines,
+                self.variant(),
+                &mut fastrand::Rng::with_seed(self.seed),
+            );
+            // the opening around the first click can leave too little room for every mine
+            self.mines = self.tiles.iter().map(|tile| tile.mines as usize).sum();
+            self.safe_tiles = self.tiles.iter().filter(|tile| tile.mines == 0).count();
+        }
+
         self.start = Instant::now();
         self.state = State::Playing;
-        self.tiles[start_col][start_row].state = TileState::Revealed
+
+        self.reveal_or_explode(start_col, start_row);
+    }
+
+    /// Reveals a tile the way clicking on it would, losing the game if it was a mine.
+    fn reveal_or_explode(&mut self, col: usize, row: usize) {
+        if self.reveal(col, row) {
+            self.explode(col, row);
+        }
+    }
+
+    /// Reveals a tile, opening up the empty space around it if it has no neighbouring mines.
+    /// Returns whether it was a mine.
+    fn reveal(&mut self, col: usize, row: usize) -> bool {
+        if self.revealed(col, row) {
+            return false;
+        }
+
+        self.reveals += 1;
+        self.open(col, row);
+
+        let now = Instant::now();
+        self.opening.push((
+            Opened {
+                col,
+                row,
+                distance: 0,
+            },
+            now,
+        ));
+
+        if self.mine(col, row) {
+            return true;
+        }
+
+        if self.opens_up(col, row) {
+            let opened = self.reveal_empty_space_at(col, row);
+            self.opening
+                .extend(opened.into_iter().map(|opened| (opened, now)));
+        }
+
+        false
+    }
+
+    /// Whether revealing a tile opens up the tiles around it. Lying clues never do, as that
+    /// would give away that the tile's true count is zero.
+    fn opens_up(&self, col: usize, row: usize) -> bool {
+        self.clues != Clues::Liar && self.neighbour_mines(col, row) == 0
+    }
+
+    /// Reveals a single tile, returning whether it was hidden before.
+    fn open(&mut self, col: usize, row: usize) -> bool {
+        let tile = &mut self.tiles[(col, row)];
+
+        match tile.state {
+            TileState::Revealed => return false,
+            TileState::Flagged => self.number_flagged -= tile.flags as usize,
+            _ => {}
+        }
+
+        tile.state = TileState::Revealed;
+        tile.flags = 0;
+        tile.revealed_on = self.reveals;
+
+        if tile.mines == 0 {
+            self.revealed_safe += 1;
+        }
+
+        self.queue_solver_work(col, row);
+        true
+    }
+
+    /// Puts flags on a tile until it has at least `flags` of them.
+    fn set_flagged(&mut self, col: usize, row: usize, flags: u8) {
+        let tile = &mut self.tiles[(col, row)];
+
+        if tile.flags < flags {
+            self.number_flagged += (flags - tile.flags) as usize;
+            tile.flags = flags;
+            tile.state = TileState::Flagged;
+            self.queue_solver_work(col, row);
+        }
+    }
+
+    /// How many more flags a tile can take.
+    fn room(&self, tile: (usize, usize)) -> u8 {
+        self.mines_per_tile - self.tiles[tile].flags
+    }
+
+    fn explode(&mut self, col: usize, row: usize) {
+        self.state = State::Dead;
+        self.reveal_all_mines();
+        self.unflagged_mines.push((col, row));
+    }
+
+    /// Queues the revealed tiles near a tile that just changed for the bot's next pass. The
+    /// 1-2-1 patterns look two tiles out, so that is how far a change can make a difference.
+    fn queue_solver_work(&mut self, col: usize, row: usize) {
+        for col_offset in -2..=2 {
+            for row_offset in -2..=2 {
+                let Some(tile) = self.offset((col, row), (col_offset, row_offset)) else {
+                    continue;
+                };
+
+                if self.tiles[tile].state == TileState::Revealed && !self.queued[tile] {
+                    self.queued[tile] = true;
+                    self.solver_work.push(tile);
+                }
+            }
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.tiles.width()
+    }
+
+    fn height(&self) -> usize {
+        self.tiles.height()
+    }
+
+    /// The tiles the clue on a tile covers, which are the tiles around it unless the clues
+    /// count something else.
+    fn surrounding_tiles(&self, col: usize, row: usize) -> Neighbours {
+        self.tiles.clue_tiles(col, row, self.topology, self.clues)
+    }
+
+    fn offset(&self, tile: (usize, usize), offset: (isize, isize)) -> Option<(usize, usize)> {
+        self.topology
+            .offset(tile, offset, self.width(), self.height())
     }
 
     fn revealed(&self, col: usize, row: usize) -> bool {
-        self.tiles[col][row].state == TileState::Revealed
+        self.tiles[(col, row)].state == TileState::Revealed
     }
 
     fn flagged(&self, col: usize, row: usize) -> bool {
-        self.tiles[col][row].state == TileState::Flagged
+        self.tiles[(col, row)].state == TileState::Flagged
     }
 
     fn mine(&self, col: usize, row: usize) -> bool {
-        self.tiles[col][row].mine
+        self.tiles[(col, row)].mines > 0
     }
 
     fn neighbour_mines(&self, col: usize, row: usize) -> u8 {
-        self.tiles[col][row].neighbour_mines_count
+        self.tiles[(col, row)].neighbour_mines_count
+    }
+
+    fn clue(&self, col: usize, row: usize) -> u8 {
+        self.tiles[(col, row)].clue
     }
 
     fn is_game_won(&self) -> bool {
-        for row in self.tiles.iter() {
-            for tile in row.iter() {
-                if !tile.mine && tile.state != TileState::Revealed {
-                    return false;
-                }
-            }
-        }
-        true
+        self.revealed_safe == self.safe_tiles
     }
 
-    fn reveal_empty_space_at(&mut self, col: usize, row: usize) {
-        let mut queue = VecDeque::<(usize, usize)>::new();
-        queue.push_back((col, row));
+    /// Ends the game once it has been won and moves the clock on, after the moves for a frame.
+    fn settle(&mut self) {
+        if self.state == State::Playing && self.is_game_won() {
+            self.state = State::Won;
+        }
 
-        while !queue.is_empty() {
-            let (current_col, current_row) = queue.pop_back().unwrap();
+        if self.state == State::Playing {
+            self.elapsed = self.start.elapsed().as_secs() as usize
+        }
+    }
 
-            for (neighbour_col, neighbour_row) in surrounding_tiles(current_col, current_row) {
-                let mut neighbour_tile = &mut self.tiles[neighbour_col][neighbour_row];
-                if neighbour_tile.neighbour_mines_count == 0
-                    && neighbour_tile.state != TileState::Revealed
-                    && !neighbour_tile.mine
-                {
-                    queue.push_back((neighbour_col, neighbour_row))
+    /// Opens up the empty space around a revealed zero, breadth first so that every tile is
+    /// visited once. Returns the tiles it opened, nearest first.
+    fn reveal_empty_space_at(&mut self, col: usize, row: usize) -> Vec<Opened> {
+        let mut opened = vec![];
+        let mut queue = VecDeque::from([(col, row, 0)]);
+
+        while let Some((current_col, current_row, distance)) = queue.pop_front() {
+            for (neighbour_col, neighbour_row) in self.surrounding_tiles(current_col, current_row) {
+                if self.flagged(neighbour_col, neighbour_row) {
+                    match self.wrong_flags {
+                        WrongFlags::Keep => continue,
+                        WrongFlags::Clear => self.wrong_flags_cleared += 1,
+                    }
+                }
+
+                if !self.open(neighbour_col, neighbour_row) {
+                    continue;
                 }
 
-                neighbour_tile.state = TileState::Revealed;
+                opened.push(Opened {
+                    col: neighbour_col,
+                    row: neighbour_row,
+                    distance: distance + 1,
+                });
+
+                if self.opens_up(neighbour_col, neighbour_row) {
+                    queue.push_back((neighbour_col, neighbour_row, distance + 1))
+                }
             }
         }
+
+        opened
     }
 
     fn reveal_all_mines(&mut self) {
-        for col in self.tiles.iter_mut() {
-            for tile in col.iter_mut() {
-                if tile.mine && tile.state != TileState::Flagged {
-                    tile.state = TileState::Revealed
-                }
+        for tile in self.tiles.iter_mut() {
+            if tile.mines > 0 && tile.state != TileState::Flagged {
+                tile.state = TileState::Revealed
             }
         }
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let textures = Textures {
-        background: load_texture!("../assets/background.png"),
-        tile: load_texture!("../assets/tile.png"),
-        flag: load_texture!("../assets/flag.png"),
-        mine: load_texture!("../assets/mine.png"),
-        cross: load_texture!("../assets/cross.png"),
-        smiley: load_texture!("../assets/smiley.png"),
-        smiley_open: load_texture!("../assets/smiley_open.png"),
-        smiley_dead: load_texture!("../assets/smiley_dead.png"),
-        smiley_clicked: load_texture!("../assets/smiley_clicked.png"),
-        smiley_glasses: load_texture!("../assets/smiley_glasses.png"),
-        neighbour_mines: [
-            load_texture!("../assets/1.png"),
-            load_texture!("../assets/2.png"),
-            load_texture!("../assets/3.png"),
-            load_texture!("../assets/4.png"),
-            load_texture!("../assets/5.png"),
-            load_texture!("../assets/6.png"),
-            load_texture!("../assets/7.png"),
-            load_texture!("../assets/8.png"),
-        ],
-        counter_digits: [
-            load_texture!("../assets/0_counter.png"),
-            load_texture!("../assets/1_counter.png"),
-            load_texture!("../assets/2_counter.png"),
-            load_texture!("../assets/3_counter.png"),
-            load_texture!("../assets/4_counter.png"),
-            load_texture!("../assets/5_counter.png"),
-            load_texture!("../assets/6_counter.png"),
-            load_texture!("../assets/7_counter.png"),
-            load_texture!("../assets/8_counter.png"),
-            load_texture!("../assets/9_counter.png"),
-        ],
+fn main() -> eyre::Result<()> {
+    let args = Args::parse();
+    let config = Config::load();
+    let mut settings = config.settings();
+    let skip_menu = args.apply(&mut settings)?;
+
+    if let Some(boards) = args.bench {
+        bitboard::bench(settings.difficulty, args.seed.unwrap_or(0), boards);
+        return Ok(());
+    }
+
+    let mut playback = None;
+    let mut practice = None;
+    let mut board = if let Some(path) = &args.replay {
+        let replay = Replay::load(path)?;
+        settings = replay.settings(&settings);
+        let board = replay.board();
+        playback = Some(replay.playback());
+        board
+    } else if let Some(path) = &args.puzzles {
+        let pack = Pack::load(path)?;
+
+        if args.check {
+            return pack.check();
+        }
+
+        let puzzles = Puzzles::new(pack);
+        let board = puzzles.board();
+        practice = Some(Practice::Puzzles(puzzles));
+        board
+    } else if args.train {
+        practice = Some(Practice::Trainer(Trainer::new()));
+        // a stand in until the trainer deals its first position
+        new_board(Difficulty::Beginner, Variant::default(), 0)
+    } else if args.tutorial {
+        let tutorial = Tutorial::new()?;
+        let board = tutorial.board();
+        practice = Some(Practice::Tutorial(tutorial));
+        board
+    } else if args.daily {
+        daily::board(daily::today())
+    } else if let Some(path) = &args.board {
+        replay::load_board(path, settings.variant)?
+    } else {
+        new_board(
+            settings.difficulty,
+            settings.variant,
+            args.seed.unwrap_or_else(|| fastrand::u64(..)),
+        )
+    };
+
+    if board.fixed_layout || playback.is_some() {
+        settings.difficulty = Difficulty::Custom {
+            width: board.width(),
+            height: board.height(),
+            mines: board.mines,
+        };
+    }
+
+    if args.tui {
+        return tui::run(settings, board, playback);
+    }
+
+    if args.protocol {
+        return protocol::run_stdio(board, settings);
+    }
+
+    if let Some(command) = &args.bot_command {
+        return protocol::run_child(board, settings, command);
+    }
+
+    if let Some(port) = args.serve {
+        let move_limit = Duration::from_secs_f32(args.move_seconds);
+        return server::run(port, board, settings, move_limit, args.games);
+    }
+
+    let mut versus = None;
+    let mut coop = None;
+
+    if let Some(port) = args.host {
+        if args.coop {
+            coop = Some(Coop::host(port, args.lives, &board, &settings)?);
+        } else {
+            versus = Some(Versus::host(port, &board)?);
+        }
+    } else if let Some(address) = &args.join {
+        if args.coop {
+            coop = Some(Coop::join(address.clone(), &board, &settings));
+        } else {
+            versus = Some(Versus::join(address.clone(), &board));
+        }
+    }
+
+    let (width, height) = if skip_menu {
+        let below = below_board(&versus, &coop, &practice);
+        game_window_size(&board_layout(&board, &settings), &settings, below)
+    } else {
+        board.state = State::Menu;
+        (MENU_WIDTH * settings.scale, MENU_HEIGHT * settings.scale)
     };
 
-    let mut board = Board::new(EXPERT_WIDTH, EXPERT_HEIGHT);
+    macroquad::Window::from_config(
+        window_conf(width, height),
+        run(config, settings, board, playback, versus, coop, practice),
+    );
+
+    Ok(())
+}
+
+async fn run(
+    config: Config,
+    mut settings: Settings,
+    mut board: Board,
+    mut playback: Option<Playback>,
+    mut versus: Option<Versus>,
+    mut coop: Option<Coop>,
+    mut practice: Option<Practice>,
+) {
+    let mut themes = ThemeSelection::new();
+    themes.select(&config.theme);
+    // the settings as the config file has them, without the flags given for this run
+    let mut saved = config.settings();
+    let mut stats = Stats::load();
+    let mut window_size = (0.0, 0.0);
+    let mut rival = Rival::new(&board, settings.mode);
 
     loop {
-        clear_background(Color::from_rgba(192, 192, 192, 255));
-        let (mouse_x, mouse_y) = mouse_position();
+        // shortcuts for the settings that are handy to flip mid game
+        if is_key_pressed(KeyCode::T) {
+            themes.next();
+        }
 
-        draw_texture_with_size(
-            &textures.background,
-            0.0,
-            0.0,
-            screen_width(),
-            screen_height(),
-        );
+        if is_key_pressed(KeyCode::P) {
+            settings.palette = settings.palette.next();
+            saved.palette = settings.palette;
+        }
 
-        draw_counter(
-            board.elapsed.min(999),
-            TIME_COUNTER_START_X,
-            MINES_COUNTER_START_Y,
-            &textures.counter_digits,
-        );
+        let layout = board_layout(&board, &settings);
 
-        draw_counter(
-            (EXPERT_MINES - board.number_flagged).max(0),
-            MINES_COUNTER_START_X,
-            MINES_COUNTER_START_Y,
-            &textures.counter_digits,
-        );
+        let requested_size = if board.state.in_menu() {
+            (MENU_WIDTH * settings.scale, MENU_HEIGHT * settings.scale)
+        } else {
+            let below = below_board(&versus, &coop, &practice);
+            game_window_size(&layout, &settings, below)
+        };
 
-        if board.state == State::Playing && board.is_game_won() {
-            board.state = State::Won;
+        if requested_size != window_size {
+            request_new_screen_size(requested_size.0, requested_size.1);
+            window_size = requested_size;
         }
 
-        if hovering_square(
-            mouse_x,
-            mouse_y,
-            SMILEY_START_X,
-            SMILEY_START_Y,
-            SMILEY_SIZE,
-        ) {
-            if is_mouse_button_pressed(MouseButton::Left) {
-                board = Board::new(EXPERT_WIDTH, EXPERT_HEIGHT);
+        let colours = themes.current().colours;
+        clear_background(colours.face);
+
+        let previous_state = board.state;
+        let before = settings.clone();
+        let transition = match board.state {
+            State::Menu => menu::main_menu(&mut settings, &colours),
+            State::Settings => menu::settings_menu(&mut settings, &mut themes),
+            State::CustomGame => menu::custom_game_menu(&mut settings, &colours),
+            State::Stats => menu::stats_menu(&settings, &stats, &colours),
+            State::Leaderboards(difficulty) => {
+                menu::leaderboards_menu(difficulty, &settings, &stats, &colours)
             }
+            State::Playing | State::Dead | State::Won | State::NewGame => {
+                match &mut practice {
+                    Some(Practice::Trainer(trainer)) => {
+                        trainer.frame(&mut board, &layout, &settings, &mut stats, themes.current())
+                    }
+                    Some(Practice::Tutorial(tutorial)) => {
+                        tutorial.frame(&mut board, &layout, &settings, themes.current())
+                    }
+                    _ => play_frame(
+                        &mut board,
+                        &mut playback,
+                        &layout,
+                        &settings,
+                        &mut stats,
+                        themes.current(),
+                        coop.as_mut(),
+                    ),
+                }
+
+                if let Some(rival) = &mut rival {
+                    rival.frame(&board, &layout, &settings, themes.current());
+                }
+
+                if let Some(versus) = &mut versus {
+                    versus.frame(&mut board, &layout, &mut settings, themes.current());
+                }
+
+                if let Some(coop) = &mut coop {
+                    coop.frame(&mut board, &layout, &settings, themes.current());
+                }
+
+                if let Some(Practice::Puzzles(puzzles)) = &mut practice {
+                    puzzles.frame(&mut board, &layout, &settings, themes.current());
+                }
+
+                if is_key_pressed(KeyCode::Escape) {
+                    Some(Transition::To(State::Menu))
+                } else {
+                    None
+                }
+            }
+        };
+
+        // only choices made in the menus are saved, so flags given for one run don't stick
+        if previous_state.in_menu() {
+            saved.keep_changes(&before, &settings);
         }
 
-        let smiley_texture = if is_mouse_button_down(MouseButton::Left) {
-            if hovering_square(
-                mouse_x,
-                mouse_y,
-                SMILEY_START_X,
-                SMILEY_START_Y,
-                SMILEY_SIZE,
-            ) {
-                &textures.smiley_clicked
-            } else {
-                &textures.smiley_open
+        let save_config = match transition {
+            Some(Transition::To(state)) => {
+                board.state = state;
+                previous_state == State::Settings
+            }
+            Some(Transition::NewGame(difficulty)) => {
+                settings.difficulty = difficulty;
+                saved.difficulty = difficulty;
+                board = new_board(difficulty, settings.variant, fastrand::u64(..));
+                playback = None;
+                practice = None;
+                rival = Rival::new(&board, settings.mode);
+                true
+            }
+            Some(Transition::Daily) => {
+                settings.difficulty = Difficulty::Expert;
+                saved.difficulty = Difficulty::Expert;
+                board = daily::board(daily::today());
+                playback = None;
+                practice = None;
+                rival = Rival::new(&board, settings.mode);
+                true
+            }
+            Some(Transition::Trainer) => {
+                practice = Some(Practice::Trainer(Trainer::new()));
+                playback = None;
+                rival = None;
+                // the trainer deals its first position on the next frame
+                board.state = State::NewGame;
+                false
+            }
+            Some(Transition::Tutorial) => {
+                match Tutorial::new() {
+                    Ok(tutorial) => {
+                        board = tutorial.board();
+                        practice = Some(Practice::Tutorial(tutorial));
+                        playback = None;
+                        rival = None;
+                    }
+                    Err(error) => eprintln!("failed to start the tutorial: {error:?}"),
+                }
+                false
+            }
+            Some(Transition::Quit) => break,
+            None => false,
+        };
+
+        if save_config {
+            if let Err(error) = Config::new(&saved, &themes.current().name).save() {
+                eprintln!("failed to save config: {error:?}");
             }
-        } else if board.state == State::Dead {
-            &textures.smiley_dead
-        } else if board.state == State::Won {
-            &textures.smiley_glasses
+        }
+
+        next_frame().await
+    }
+}
+
+/// Boards dealt one after another for practice, rather than games picked from the menu.
+enum Practice {
+    Puzzles(Puzzles),
+    Trainer(Trainer),
+    Tutorial(Tutorial),
+}
+
+fn new_board(difficulty: Difficulty, variant: Variant, seed: u64) -> Board {
+    Board::new(
+        difficulty.width(),
+        difficulty.height(),
+        difficulty.mines(),
+        seed,
+        variant,
+    )
+}
+
+/// Room for the board, for a second one beside it when racing, and for `below` unscaled
+/// pixels under it.
+fn game_window_size(layout: &Layout, settings: &Settings, below: f32) -> (f32, f32) {
+    let boards = if settings.mode.racing() { 2.0 } else { 1.0 };
+
+    (
+        boards * layout.window_width,
+        layout.window_height + below * layout.scale,
+    )
+}
+
+/// How much room goes under the board, in unscaled pixels: the tutorial's lessons, or the
+/// strip showing how the others are doing when playing over the network or practising.
+fn below_board(versus: &Option<Versus>, coop: &Option<Coop>, practice: &Option<Practice>) -> f32 {
+    match practice {
+        Some(Practice::Tutorial(_)) => tutorial::PANEL_HEIGHT,
+        Some(_) => versus::STATUS_HEIGHT,
+        None if versus.is_some() || coop.is_some() => versus::STATUS_HEIGHT,
+        None => 0.0,
+    }
+}
+
+fn board_layout(board: &Board, settings: &Settings) -> Layout {
+    Layout::new(
+        board.width(),
+        board.height(),
+        settings.scale,
+        board.topology,
+        board.topology == Topology::Torus && settings.ghost_edges,
+    )
+}
+
+fn play_frame(
+    board: &mut Board,
+    playback: &mut Option<Playback>,
+    layout: &Layout,
+    settings: &Settings,
+    stats: &mut Stats,
+    theme: &Theme,
+    mut coop: Option<&mut Coop>,
+) {
+    let (mouse_x, mouse_y) = mouse_position();
+    let textures = &theme.textures;
+
+    draw_frame(layout, &theme.colours);
+    draw_counters(board, layout, textures);
+
+    if layout.hovering_smiley(mouse_x, mouse_y) && is_mouse_button_pressed(MouseButton::Left) {
+        *board = board.restart();
+        *playback = None;
+    }
+
+    let smiley_texture = if is_mouse_button_down(MouseButton::Left) {
+        if layout.hovering_smiley(mouse_x, mouse_y) {
+            &textures.smiley_clicked
         } else {
-            &textures.smiley
+            &textures.smiley_open
+        }
+    } else {
+        smiley_for(board.state, textures)
+    };
+
+    draw_smiley(smiley_texture, layout);
+
+    let previous_state = board.state;
+
+    match playback {
+        Some(playback) => {
+            for action in playback.due() {
+                board.apply(action, settings);
+            }
+        }
+        // moves on a shared board wait for the host to put them in order
+        None => match &mut coop {
+            Some(coop) => {
+                if let Some(action) = board.input_action(layout, settings, mouse_x, mouse_y) {
+                    coop.request(action);
+                }
+            }
+            None => board.update(layout, settings, mouse_x, mouse_y),
+        },
+    }
+
+    board.settle();
+
+    let finished = matches!(previous_state, State::Playing | State::NewGame)
+        && matches!(board.state, State::Won | State::Dead)
+        && playback.is_none();
+
+    if finished {
+        // a shared board isn't anyone's own game to count in the stats
+        let notes = match coop {
+            Some(coop) => save_replay(board, coop.rules()).into_iter().collect(),
+            None => finish_game(board, settings, stats),
         };
 
-        draw_texture_with_size(
-            smiley_texture,
-            SMILEY_START_X,
-            SMILEY_START_Y,
-            SMILEY_SIZE,
-            SMILEY_SIZE,
+        for note in notes {
+            println!("{note}");
+        }
+    }
+
+    board.look(settings.fog, layout, mouse_x, mouse_y);
+    board.fade(get_frame_time());
+    draw_tiles(board, layout, theme, settings);
+
+    if board.daily.is_some() && board.state == State::NewGame {
+        let (col, row) = daily::start_tile();
+        let colour = settings.palette.flag_colour();
+        outline_tile_shape(layout, col, row, 2.0 * layout.scale, colour);
+    }
+}
+
+/// The mines left and the seconds so far, in the counters either side of the smiley.
+fn draw_counters(board: &Board, layout: &Layout, textures: &Textures) {
+    draw_counter(
+        board.elapsed.min(999),
+        layout.time_counter_start_x,
+        layout,
+        &textures.counter_digits,
+    );
+
+    draw_counter(
+        board.mines.saturating_sub(board.number_flagged),
+        layout.mines_counter_start_x,
+        layout,
+        &textures.counter_digits,
+    );
+}
+
+/// The face the smiley makes at a game in `state` when nobody is clicking.
+fn smiley_for(state: State, textures: &Textures) -> &Texture2D {
+    match state {
+        State::Dead => &textures.smiley_dead,
+        State::Won => &textures.smiley_glasses,
+        _ => &textures.smiley,
+    }
+}
+
+fn draw_smiley(texture: &Texture2D, layout: &Layout) {
+    draw_texture_with_size(
+        texture,
+        layout.smiley_start_x,
+        layout.smiley_start_y,
+        layout.smiley_size,
+        layout.smiley_size,
+    );
+}
+
+/// Saves the replay and records the stats for a game that just ended, returning what there is
+/// to tell the player about it.
+fn finish_game(board: &Board, settings: &Settings, stats: &mut Stats) -> Vec<String> {
+    let mut notes = vec![];
+
+    // the bot's moves aren't recorded, so there is nothing to replay
+    if settings.mode != Mode::Bot {
+        notes.extend(save_replay(board, settings));
+    }
+
+    // only games played entirely by hand count towards the stats, and puzzles are not games
+    // to compare
+    if settings.mode == Mode::Human && board.given.is_none() {
+        let seconds = board.start.elapsed().as_secs_f32();
+        let won = board.state == State::Won;
+
+        if let Some(position) = stats.record(
+            settings.difficulty,
+            won,
+            seconds,
+            board.revealed_safe,
+            board.wrong_flags_cleared,
+        ) {
+            notes.push(format!(
+                "{} in {}, #{} on the leaderboard",
+                settings.difficulty.name(),
+                settings.timer_precision.format(seconds),
+                position + 1
+            ));
+        }
+
+        if let Some(day) = board.daily {
+            notes.extend(daily::finish(board, day, seconds, stats));
+        }
+
+        if let Err(error) = stats.save() {
+            notes.push(format!("failed to save stats: {error:?}"));
+        }
+    }
+
+    notes
+}
+
+/// Saves the replay of a game that just ended, returning what there is to say about it.
+fn save_replay(board: &Board, settings: &Settings) -> Option<String> {
+    match Replay::new(board, settings).save() {
+        Ok(Some(path)) => Some(format!("replay saved to {}", path.display())),
+        Ok(None) => None,
+        Err(error) => Some(format!("failed to save replay: {error:?}")),
+    }
+}
+
+/// Draws the window chrome around the board: the raised outer border, the sunken header
+/// and board wells, the counter wells, the smiley button and the grid under revealed tiles.
+fn draw_frame(layout: &Layout, colours: &Colours) {
+    let scale = layout.scale;
+    let (board_width, board_height) = (layout.board_width, layout.board_height);
+
+    draw_bevel(
+        layout.origin_x,
+        0.0,
+        layout.window_width,
+        layout.window_height,
+        OUTER_BEVEL * scale,
+        colours.highlight,
+        colours.shadow,
+    );
+
+    draw_bevel(
+        layout.origin_x + PANEL_MARGIN * scale,
+        PANEL_MARGIN * scale,
+        layout.panel_width,
+        HEADER_HEIGHT * scale,
+        HEADER_BEVEL * scale,
+        colours.shadow,
+        colours.highlight,
+    );
+
+    for counter_start_x in [layout.mines_counter_start_x, layout.time_counter_start_x] {
+        draw_bevel(
+            counter_start_x - scale,
+            layout.counter_start_y - scale,
+            3.0 * layout.counter_digit_width + 2.0 * scale,
+            layout.counter_digit_height + 2.0 * scale,
+            scale,
+            colours.shadow,
+            colours.highlight,
         );
+    }
+
+    draw_rectangle(
+        layout.smiley_start_x,
+        layout.smiley_start_y,
+        layout.smiley_size,
+        layout.smiley_size,
+        colours.shadow,
+    );
+    draw_bevel(
+        layout.smiley_start_x + scale,
+        layout.smiley_start_y + scale,
+        layout.smiley_size - 2.0 * scale,
+        layout.smiley_size - 2.0 * scale,
+        HEADER_BEVEL * scale,
+        colours.highlight,
+        colours.shadow,
+    );
+
+    draw_bevel(
+        layout.board_start_x - BOARD_BEVEL * scale,
+        layout.board_start_y - BOARD_BEVEL * scale,
+        layout.panel_width,
+        board_height + 2.0 * BOARD_BEVEL * scale,
+        BOARD_BEVEL * scale,
+        colours.shadow,
+        colours.highlight,
+    );
+
+    // other shapes of tile draw their own outlines
+    if !layout.topology.square() {
+        return;
+    }
 
-        board.update(mouse_x, mouse_y);
+    // revealed tiles show the face colour with a one pixel shadow along their top and left
+    let mut x = layout.board_start_x;
+    while x < layout.board_start_x + board_width {
+        draw_rectangle(x, layout.board_start_y, scale, board_height, colours.shadow);
+        x += layout.tile_size;
+    }
+
+    let mut y = layout.board_start_y;
+    while y < layout.board_start_y + board_height {
+        draw_rectangle(layout.board_start_x, y, board_width, scale, colours.shadow);
+        y += layout.tile_size;
+    }
+}
 
-        draw_tiles(&mut board, &textures);
+/// Draws a classic bevel `thickness` wide just inside the given rectangle. Swapping the two
+/// colours turns a raised bevel into a sunken one.
+fn draw_bevel(
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    thickness: f32,
+    top_left: Color,
+    bottom_right: Color,
+) {
+    draw_rectangle(x, y, width, thickness, top_left);
+    draw_rectangle(x, y, thickness, height, top_left);
+    draw_rectangle(x, y + height - thickness, width, thickness, bottom_right);
+    draw_rectangle(x + width - thickness, y, thickness, height, bottom_right);
+
+    // mitre the bottom left and top right corners along the diagonal
+    draw_triangle(
+        vec2(x, y + height),
+        vec2(x + thickness, y + height - thickness),
+        vec2(x + thickness, y + height),
+        bottom_right,
+    );
+    draw_triangle(
+        vec2(x + width, y),
+        vec2(x + width - thickness, y + thickness),
+        vec2(x + width - thickness, y),
+        top_left,
+    );
+}
 
-        if board.state == State::Playing {
-            board.elapsed = board.start.elapsed().as_secs() as usize
+fn draw_tiles(board: &mut Board, layout: &Layout, theme: &Theme, settings: &Settings) {
+    for row in 0..board.height() {
+        for col in 0..board.width() {
+            draw_tile(board, layout, theme, settings, col, row);
         }
+    }
 
-        next_frame().await
+    if layout.ghost_edges {
+        draw_ghost_edges(board, layout, theme, settings);
     }
+
+    // freshly opened tiles shrink away, a step later for each tile further from the click
+    board.opening.retain(|(opened, at)| {
+        let progress = (at.elapsed().as_secs_f32() - opened.distance as f32 * RIPPLE_STEP_SECONDS)
+            / RIPPLE_SECONDS;
+
+        if progress >= 1.0 {
+            return false;
+        }
+
+        draw_hidden_tile(
+            layout,
+            theme,
+            opened.col,
+            opened.row,
+            1.0 - progress.max(0.0),
+        );
+
+        true
+    });
 }
 
-fn draw_tiles(board: &mut Board, textures: &Textures) {
-    for row in 0..EXPERT_HEIGHT {
-        for col in 0..EXPERT_WIDTH {
-            if board.state == State::Dead && board.mine(col, row) {
-                if board.unflagged_mines.contains(&(col, row)) {
-                    draw_rectangle(
-                        TILE_START_X + 1.0 + col as f32 * TILE_SIZE,
-                        TILE_START_Y + 1.0 + row as f32 * TILE_SIZE,
-                        TILE_SIZE - 1.0,
-                        TILE_SIZE - 1.0,
-                        RED,
-                    )
-                }
+fn draw_tile(
+    board: &mut Board,
+    layout: &Layout,
+    theme: &Theme,
+    settings: &Settings,
+    col: usize,
+    row: usize,
+) {
+    let palette = settings.palette;
+    let textures = &theme.textures;
+
+    if !layout.topology.square() {
+        outline_tile_shape(layout, col, row, layout.scale, theme.colours.shadow);
+    }
 
-                draw_at_tile(&textures.mine, col, row)
-            }
+    if board.state == State::Dead && board.mine(col, row) {
+        if board.unflagged_mines.contains(&(col, row)) {
+            draw_exploded(layout, palette, col, row)
+        }
 
-            if board.revealed(col, row) {
-                let neighbour_mines_count = board.tiles[col][row].neighbour_mines_count;
+        draw_at_tile(&textures.mine, layout, col, row);
+        draw_stack_count(layout, board.tiles[(col, row)].mines, col, row);
+    }
 
-                if neighbour_mines_count != 0 && !board.mine(col, row) && !board.flagged(col, row) {
-                    draw_at_tile(
-                        &textures.neighbour_mines[neighbour_mines_count as usize - 1],
-                        col,
-                        row,
-                    )
-                }
+    if board.revealed(col, row) {
+        let clue = board.clue(col, row);
+
+        // a lying 0 still has to be shown, only honest ones are left blank
+        let shown = clue != 0 || board.clues == Clues::Liar;
+        if shown && !board.mine(col, row) && !board.flagged(col, row) {
+            // there are only textures from 1 up to 8
+            if palette.uses_glyphs(settings.number_style)
+                || clue == 0
+                || clue as usize > textures.neighbour_mines.len()
+            {
+                draw_number_glyph(clue, layout, palette, col, row)
+            } else {
+                draw_at_tile(
+                    &textures.neighbour_mines[clue as usize - 1],
+                    layout,
+                    col,
+                    row,
+                )
+            }
+
+            let visibility = board.visibility[(col, row)];
+            if visibility < 1.0 {
+                draw_fogged(layout, &theme.colours, col, row, 1.0 - visibility);
             }
+        }
+    }
+
+    if board.state == State::Dead && board.flagged(col, row) && !board.mine(col, row) {
+        board.tiles[(col, row)].state = TileState::Revealed;
+        draw_at_tile(&textures.mine, layout, col, row);
+        draw_at_tile(&textures.cross, layout, col, row);
+    } else if !board.revealed(col, row) {
+        draw_hidden_tile(layout, theme, col, row, 1.0);
 
-            if board.state == State::Dead && board.flagged(col, row) && !board.mine(col, row) {
-                board.tiles[col][row].state = TileState::Revealed;
-                draw_at_tile(&textures.mine, col, row);
-                draw_at_tile(&textures.cross, col, row);
+        if board.flagged(col, row) {
+            if palette == Palette::Classic {
+                draw_at_tile(&textures.flag, layout, col, row)
             } else {
-                if !board.revealed(col, row) {
-                    draw_at_tile(&textures.tile, col, row);
+                draw_flag(layout, palette, col, row)
+            }
 
-                    if board.flagged(col, row) {
-                        draw_at_tile(&textures.flag, col, row)
-                    }
-                }
+            draw_stack_count(layout, board.tiles[(col, row)].flags, col, row);
+        } else if board.tiles[(col, row)].state == TileState::Question {
+            draw_question_mark(layout, col, row)
+        }
+    }
+}
+
+/// Draws a hidden tile shrunk about its middle to `size` times its full size. Squares use the
+/// theme's tile texture, and other shapes are drawn raised in the theme's colours.
+fn draw_hidden_tile(layout: &Layout, theme: &Theme, col: usize, row: usize, size: f32) {
+    if layout.topology.square() {
+        let (x, y) = layout.tile_position(col, row);
+        let inset = layout.tile_size * (1.0 - size) / 2.0;
+        draw_texture_with_size(
+            &theme.textures.tile,
+            x + inset,
+            y + inset,
+            layout.tile_size * size,
+            layout.tile_size * size,
+        );
+        return;
+    }
+
+    let colours = &theme.colours;
+    let bevel = 2.0 * layout.scale / tile_inradius(layout, col, row);
+
+    fill_tile_shape(layout, col, row, size, 0.0, colours.shadow);
+    fill_tile_shape(
+        layout,
+        col,
+        row,
+        size * (1.0 - bevel / 2.0),
+        -layout.scale * size,
+        colours.highlight,
+    );
+    fill_tile_shape(
+        layout,
+        col,
+        row,
+        size * (1.0 - 1.5 * bevel),
+        0.0,
+        colours.face,
+    );
+}
+
+/// Fills in a tile that isn't square, shrunk about its middle to `size` times its full size
+/// and moved `offset` pixels right and down.
+fn fill_tile_shape(layout: &Layout, col: usize, row: usize, size: f32, offset: f32, colour: Color) {
+    let (corners, count) = layout.tile_corners(col, row);
+    let centre = Vec2::from(layout.tile_centre(col, row));
+    let place = |corner: Vec2| centre + (corner - centre) * size + Vec2::splat(offset);
+
+    for i in 0..count {
+        draw_triangle(
+            place(centre),
+            place(corners[i]),
+            place(corners[(i + 1) % count]),
+            colour,
+        );
+    }
+}
+
+fn outline_tile_shape(layout: &Layout, col: usize, row: usize, thickness: f32, colour: Color) {
+    let (corners, count) = layout.tile_corners(col, row);
+
+    for i in 0..count {
+        let (from, to) = (corners[i], corners[(i + 1) % count]);
+        draw_line(from.x, from.y, to.x, to.y, thickness, colour);
+    }
+}
+
+/// Distance from the middle of a tile to the middle of its sides.
+fn tile_inradius(layout: &Layout, col: usize, row: usize) -> f32 {
+    let (corners, _) = layout.tile_corners(col, row);
+    Vec2::from(layout.tile_centre(col, row)).distance((corners[0] + corners[1]) / 2.0)
+}
+
+/// Draws a shaded copy of the opposite edge in the ring around a wrapping board, so that the
+/// tiles across the wrap can be seen next to the ones they touch. Each ghost is drawn as its
+/// real tile on a layout shifted by the width or height of the board.
+fn draw_ghost_edges(board: &mut Board, layout: &Layout, theme: &Theme, settings: &Settings) {
+    let (width, height) = (board.width() as isize, board.height() as isize);
+
+    for ghost_row in -1..=height {
+        for ghost_col in -1..=width {
+            if (0..width).contains(&ghost_col) && (0..height).contains(&ghost_row) {
+                continue;
             }
+
+            let col = ghost_col.rem_euclid(width);
+            let row = ghost_row.rem_euclid(height);
+            let shifted = Layout {
+                tile_start_x: layout.tile_start_x + (ghost_col - col) as f32 * layout.tile_size,
+                tile_start_y: layout.tile_start_y + (ghost_row - row) as f32 * layout.tile_size,
+                ..layout.clone()
+            };
+
+            draw_tile(board, &shifted, theme, settings, col as usize, row as usize);
+            draw_rectangle(
+                layout.tile_start_x + ghost_col as f32 * layout.tile_size,
+                layout.tile_start_y + ghost_row as f32 * layout.tile_size,
+                layout.tile_size,
+                layout.tile_size,
+                GHOST_SHADE,
+            );
+        }
+    }
+}
+
+/// Highlights the mine that lost the game. Monochrome can't rely on the fill colour, so it
+/// also gets a heavy border.
+fn draw_exploded(layout: &Layout, palette: Palette, col: usize, row: usize) {
+    if !layout.topology.square() {
+        let size = 1.0 - layout.scale / tile_inradius(layout, col, row);
+        fill_tile_shape(layout, col, row, size, 0.0, palette.exploded_colour());
+
+        if palette == Palette::Monochrome {
+            outline_tile_shape(layout, col, row, 3.0 * layout.scale, BLACK);
         }
+        return;
+    }
+
+    let (x, y) = layout.tile_position(col, row);
+    let (x, y) = (x + layout.scale, y + layout.scale);
+    let size = layout.tile_size - layout.scale;
+
+    draw_rectangle(x, y, size, size, palette.exploded_colour());
+
+    if palette == Palette::Monochrome {
+        draw_rectangle_lines(x, y, size, size, 3.0 * layout.scale, BLACK);
     }
 }
 
-#[macro_export]
-macro_rules! load_texture {
-    ( $path:tt ) => {
-        Texture2D::from_file_with_format(include_bytes!($path), None)
+fn draw_number_glyph(
+    neighbour_mines: u8,
+    layout: &Layout,
+    palette: Palette,
+    col: usize,
+    row: usize,
+) {
+    let text = neighbour_mines.to_string();
+    let font_size = (layout.tile_size * 1.1) as u16;
+    let dimensions = measure_text(&text, None, font_size, 1.0);
+    let (x, y) = layout.tile_position(col, row);
+    let x = x + (layout.tile_size - dimensions.width) / 2.0;
+    let y = y + (layout.tile_size + dimensions.offset_y) / 2.0;
+    let colour = palette.number_colour(neighbour_mines);
+
+    // drawn twice to get close to the weight of the classic digits
+    draw_text(&text, x, y, font_size as f32, colour);
+    draw_text(&text, x + layout.scale, y, font_size as f32, colour);
+}
+
+fn draw_question_mark(layout: &Layout, col: usize, row: usize) {
+    let font_size = (layout.tile_size * 1.1) as u16;
+    let dimensions = measure_text("?", None, font_size, 1.0);
+    let (x, y) = layout.tile_position(col, row);
+    let x = x + (layout.tile_size - dimensions.width) / 2.0;
+    let y = y + (layout.tile_size + dimensions.offset_y) / 2.0;
+
+    draw_text("?", x, y, font_size as f32, BLACK);
+    draw_text("?", x + layout.scale, y, font_size as f32, BLACK);
+}
+
+/// Covers a number that has gone into the fog, `fog` of the way, leaving a dot to show there
+/// was a number there.
+fn draw_fogged(layout: &Layout, colours: &Colours, col: usize, row: usize, fog: f32) {
+    let face = Color {
+        a: fog,
+        ..colours.face
     };
+    let shadow = Color {
+        a: fog,
+        ..colours.shadow
+    };
+
+    if layout.topology.square() {
+        let (x, y) = layout.tile_position(col, row);
+        let size = layout.tile_size - layout.scale;
+        draw_rectangle(x + layout.scale, y + layout.scale, size, size, face);
+    } else {
+        let size = 1.0 - layout.scale / tile_inradius(layout, col, row);
+        fill_tile_shape(layout, col, row, size, 0.0, face);
+    }
+
+    let (x, y) = layout.tile_centre(col, row);
+    draw_circle(x, y, 1.5 * layout.scale, shadow);
 }
 
-fn draw_at_tile(texture: &Texture2D, col: usize, row: usize) {
-    draw_texture_with_size(
-        texture,
-        TILE_START_X + col as f32 * TILE_SIZE,
-        TILE_START_Y + row as f32 * TILE_SIZE,
-        TILE_SIZE,
-        TILE_SIZE,
-    )
+/// Shows how many mines or flags a tile holding more than one has, small in its bottom right.
+fn draw_stack_count(layout: &Layout, count: u8, col: usize, row: usize) {
+    if count < 2 {
+        return;
+    }
+
+    let text = count.to_string();
+    let font_size = (layout.tile_size * 0.6) as u16;
+    let dimensions = measure_text(&text, None, font_size, 1.0);
+    let (x, y) = layout.tile_position(col, row);
+
+    draw_text(
+        &text,
+        x + layout.tile_size - dimensions.width - layout.scale,
+        y + layout.tile_size - layout.scale,
+        font_size as f32,
+        BLACK,
+    );
+}
+
+/// A flag drawn from shapes so its pennant can take the palette's colour.
+fn draw_flag(layout: &Layout, palette: Palette, col: usize, row: usize) {
+    let unit = layout.tile_size / 16.0;
+    let (x, y) = layout.tile_position(col, row);
+
+    draw_triangle(
+        vec2(x + 9.0 * unit, y + 3.0 * unit),
+        vec2(x + 9.0 * unit, y + 8.0 * unit),
+        vec2(x + 4.0 * unit, y + 5.5 * unit),
+        palette.flag_colour(),
+    );
+    draw_rectangle(x + 8.0 * unit, y + 3.0 * unit, unit, 7.0 * unit, BLACK);
+    draw_rectangle(x + 6.0 * unit, y + 10.0 * unit, 4.0 * unit, unit, BLACK);
+    draw_rectangle(
+        x + 4.0 * unit,
+        y + 11.0 * unit,
+        8.0 * unit,
+        2.0 * unit,
+        BLACK,
+    );
+}
+
+fn draw_at_tile(texture: &Texture2D, layout: &Layout, col: usize, row: usize) {
+    let (x, y) = layout.tile_position(col, row);
+
+    draw_texture_with_size(texture, x, y, layout.tile_size, layout.tile_size)
 }
 
-fn draw_counter(number: usize, x: f32, y: f32, textures: &[Texture2D]) {
+fn draw_counter(number: usize, x: f32, layout: &Layout, textures: &[Texture2D]) {
     for i in 0..3 {
         draw_texture_with_size(
             &textures[(number / 10_usize.pow(2 - i)) % 10],
-            x + COUNTER_DIGIT_WIDTH * i as f32,
-            y,
-            COUNTER_DIGIT_WIDTH,
-            COUNTER_DIGIT_HEIGHT,
+            x + layout.counter_digit_width * i as f32,
+            layout.counter_start_y,
+            layout.counter_digit_width,
+            layout.counter_digit_height,
         );
     }
 }
@@ -606,43 +2159,65 @@ fn draw_texture_with_size(texture: &Texture2D, x: f32, y: f32, width: f32, heigh
     )
 }
 
-fn generate_fair_game(start_col: usize, start_row: usize) -> Vec<Vec<Tile>> {
-    loop {
-        let tiles = generate_game();
-
-        if tiles[start_col][start_row].neighbour_mines_count == 0
-            && !tiles[start_col][start_row].mine
-        {
-            return tiles;
-        }
+/// Places the mines anywhere except on and around the first click, so that it always opens
+/// up some space. This picks uniformly from the same boards as regenerating until the first
+/// click lands on a zero would. When tiles can hold several mines, every tile is a candidate
+/// once for each mine it could hold.
+fn generate_fair_game(
+    start_col: usize,
+    start_row: usize,
+    width: usize,
+    height: usize,
+    mines: usize,
+    variant: Variant,
+    rng: &mut fastrand::Rng,
+) -> Grid<Tile> {
+    let mut tiles = Grid::new(width, height, Tile::default());
+
+    let opening = tiles
+        .clue_tiles(start_col, start_row, variant.topology, variant.clues)
+        .chain([(start_col, start_row)])
+        .collect::<Vec<_>>();
+    let mut candidates = tiles
+        .positions()
+        .filter(|tile| !opening.contains(tile))
+        .flat_map(|tile| std::iter::repeat_n(tile, variant.mines_per_tile as usize))
+        .collect::<Vec<_>>();
+
+    // partial Fisher-Yates shuffle, the first `mines` candidates get a mine
+    let mines = mines.min(candidates.len());
+    for i in 0..mines {
+        let j = rng.usize(i..candidates.len());
+        candidates.swap(i, j);
+        tiles[candidates[i]].mines += 1;
     }
-}
 
-fn generate_game() -> Vec<Vec<Tile>> {
-    let mut tiles = vec![vec![Tile::default(); EXPERT_HEIGHT]; EXPERT_WIDTH];
+    count_neighbour_mines(&mut tiles, variant, rng);
 
-    let mut placed_mines = 0;
+    tiles
+}
 
-    while placed_mines < EXPERT_MINES {
-        let col = fastrand::usize(0..EXPERT_WIDTH);
-        let row = fastrand::usize(0..EXPERT_HEIGHT);
+fn count_neighbour_mines(tiles: &mut Grid<Tile>, variant: Variant, rng: &mut fastrand::Rng) {
+    for (col, row) in tiles.positions() {
+        let mines = tiles
+            .clue_tiles(col, row, variant.topology, variant.clues)
+            .map(|neighbour| tiles[neighbour].mines)
+            .sum();
 
-        if !tiles[col][row].mine {
-            tiles[col][row].mine = true;
-            placed_mines += 1;
-        }
+        let tile = &mut tiles[(col, row)];
+        tile.neighbour_mines_count = mines;
+        tile.clue = variant.clues.shown(mines, rng);
     }
+}
 
-    for col in 0..tiles.len() {
-        for row in 0..tiles[0].len() {
-            tiles[col][row].neighbour_mines_count = surrounding_tiles(col, row)
-                .into_iter()
-                .filter(|(col, row)| tiles[*col][*row].mine)
-                .count() as u8
-        }
-    }
+/// Distance from the middle of a hex to its corners, for a hex `width` across its flat sides.
+fn hex_radius(width: f32) -> f32 {
+    width / 3.0_f32.sqrt()
+}
 
-    tiles
+/// Height of a triangle with sides twice `tile_size` long.
+fn triangle_height(tile_size: f32) -> f32 {
+    tile_size * 3.0_f32.sqrt()
 }
 
 fn hovering_square(mouse_x: f32, mouse_y: f32, start_x: f32, start_y: f32, size: f32) -> bool {
@@ -652,45 +2227,58 @@ fn hovering_square(mouse_x: f32, mouse_y: f32, start_x: f32, start_y: f32, size:
         && mouse_y <= start_y + size
 }
 
-fn hovering_tile(mouse_x: f32, mouse_y: f32, col: usize, row: usize) -> bool {
-    let tile_x = TILE_START_X + col as f32 * TILE_SIZE;
-    let tile_y = TILE_START_Y + row as f32 * TILE_SIZE;
-
-    hovering_square(mouse_x, mouse_y, tile_x, tile_y, TILE_SIZE)
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn surrounding_bounds(col: usize, row: usize) -> ((usize, usize), (usize, usize)) {
-    let min_row = if row == 0 { 0 } else { row - 1 };
-    let max_row = if row == EXPERT_HEIGHT - 1 {
-        EXPERT_HEIGHT - 1
-    } else {
-        row + 1
-    };
-
-    let min_col = if col == 0 { 0 } else { col - 1 };
-    let max_col = if col == EXPERT_WIDTH - 1 {
-        EXPERT_WIDTH - 1
-    } else {
-        col + 1
-    };
-
-    ((min_col, min_row), (max_col, max_row))
-}
+    fn board(rows: &[&str], variant: Variant) -> Board {
+        let (layout, _) = replay::parse_rows(rows).unwrap();
+        Board::with_layout(&layout, variant)
+    }
 
-fn surrounding_tiles(col: usize, row: usize) -> Vec<(usize, usize)> {
-    let ((min_col, min_row), (max_col, max_row)) = surrounding_bounds(col, row);
+    #[test]
+    fn zeros_open_up_the_space_around_them() {
+        let mut board = board(&["....", "....", "...*"], Variant::default());
+        board.apply(Action::Reveal(0, 0), &Settings::default());
 
-    let mut tiles = Vec::<(usize, usize)>::new();
+        assert_eq!(board.revealed_safe, board.safe_tiles);
+    }
 
-    for col_2 in min_col..=max_col {
-        for row_2 in min_row..=max_row {
-            if col_2 == col && row_2 == row {
-                continue;
-            }
+    #[test]
+    fn lying_clues_never_open_up() {
+        let variant = Variant {
+            clues: Clues::Liar,
+            ..Variant::default()
+        };
+        let mut board = board(&["....", "....", "...*"], variant);
+        board.apply(Action::Reveal(0, 0), &Settings::default());
 
-            tiles.push((col_2, row_2))
-        }
+        assert_eq!(board.revealed_safe, 1);
+        assert_ne!(board.clue(0, 0), 0);
     }
 
-    tiles
+    #[test]
+    fn the_fog_is_only_worked_out_again_when_it_can_change() {
+        let mut board = board(&["*..*", "....", "*..*"], Variant::default());
+        let settings = Settings::default();
+        board.apply(Action::Reveal(1, 1), &settings);
+        assert_eq!(board.state, State::Playing);
+
+        let layout = board_layout(&board, &settings);
+        let (x, y) = layout.tile_centre(0, 0);
+        board.look(Fog::Cursor, &layout, x, y);
+        assert!(board.in_sight[(0, 0)] && !board.in_sight[(3, 2)]);
+
+        // moving within the same tile changes nothing, so the fog is left as it was
+        board.in_sight[(0, 0)] = false;
+        board.look(Fog::Cursor, &layout, x + 1.0, y + 1.0);
+        assert!(!board.in_sight[(0, 0)]);
+
+        let (x, y) = layout.tile_centre(3, 2);
+        board.look(Fog::Cursor, &layout, x, y);
+        assert!(!board.in_sight[(0, 0)] && board.in_sight[(3, 2)]);
+
+        board.look(Fog::Off, &layout, x, y);
+        assert!(board.in_sight.iter().all(|&in_sight| in_sight));
+    }
 }
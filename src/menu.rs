@@ -0,0 +1,418 @@
+use macroquad::prelude::*;
+
+use crate::{
+    daily, draw_bevel,
+    palette::NumberStyle,
+    settings::{Difficulty, Settings, MAX_CUSTOM_HEIGHT, MAX_CUSTOM_WIDTH},
+    stats::{format_date, Stats},
+    theme::{Colours, ThemeSelection},
+    State,
+};
+
+// menu measurements in unscaled pixels
+pub const MENU_WIDTH: f32 = 320.0;
+pub const MENU_HEIGHT: f32 = 456.0;
+const BUTTON_WIDTH: f32 = 200.0;
+const BUTTON_HEIGHT: f32 = 20.0;
+const BUTTON_SPACING: f32 = 24.0;
+const FONT_SIZE: f32 = 16.0;
+const TITLE_FONT_SIZE: f32 = 28.0;
+const FIRST_ROW_Y: f32 = 56.0;
+
+/// What the main loop has to do after a menu screen handled a click.
+pub enum Transition {
+    To(State),
+    NewGame(Difficulty),
+    /// Today's daily board.
+    Daily,
+    /// Positions from the pattern trainer.
+    Trainer,
+    /// The lessons for new players.
+    Tutorial,
+    Quit,
+}
+
+/// Minimal immediate mode widgets drawn in the same bevelled style as the board.
+struct Ui<'a> {
+    scale: f32,
+    colours: &'a Colours,
+    mouse_x: f32,
+    mouse_y: f32,
+}
+
+impl<'a> Ui<'a> {
+    fn new(scale: f32, colours: &'a Colours) -> Self {
+        let (mouse_x, mouse_y) = mouse_position();
+
+        Self {
+            scale,
+            colours,
+            mouse_x,
+            mouse_y,
+        }
+    }
+
+    fn centred_text(&self, text: &str, y: f32, font_size: f32) {
+        let font_size = (font_size * self.scale) as u16;
+        let dimensions = measure_text(text, None, font_size, 1.0);
+
+        draw_text(
+            text,
+            (MENU_WIDTH * self.scale - dimensions.width) / 2.0,
+            y * self.scale,
+            font_size as f32,
+            self.colours.text,
+        );
+    }
+
+    fn text(&self, text: &str, x: f32, y: f32) {
+        draw_text(
+            text,
+            x * self.scale,
+            y * self.scale,
+            FONT_SIZE * self.scale,
+            self.colours.text,
+        );
+    }
+
+    /// Draws a button at unscaled coordinates and returns whether it was clicked this frame.
+    fn button_at(&self, label: &str, x: f32, y: f32, width: f32) -> bool {
+        let (x, y, width, height) = (
+            x * self.scale,
+            y * self.scale,
+            width * self.scale,
+            BUTTON_HEIGHT * self.scale,
+        );
+        let hovered = self.mouse_x >= x
+            && self.mouse_x <= x + width
+            && self.mouse_y >= y
+            && self.mouse_y <= y + height;
+        let pressed = hovered && is_mouse_button_down(MouseButton::Left);
+
+        let (top_left, bottom_right) = if pressed {
+            (self.colours.shadow, self.colours.highlight)
+        } else {
+            (self.colours.highlight, self.colours.shadow)
+        };
+        draw_rectangle(x, y, width, height, self.colours.face);
+        draw_bevel(
+            x,
+            y,
+            width,
+            height,
+            2.0 * self.scale,
+            top_left,
+            bottom_right,
+        );
+
+        let font_size = (FONT_SIZE * self.scale) as u16;
+        let dimensions = measure_text(label, None, font_size, 1.0);
+        draw_text(
+            label,
+            x + (width - dimensions.width) / 2.0,
+            y + (height + dimensions.offset_y) / 2.0,
+            font_size as f32,
+            self.colours.text,
+        );
+
+        hovered && is_mouse_button_released(MouseButton::Left)
+    }
+
+    /// A centred button in the given row of the menu.
+    fn button(&self, label: &str, row: usize) -> bool {
+        self.button_at(
+            label,
+            (MENU_WIDTH - BUTTON_WIDTH) / 2.0,
+            FIRST_ROW_Y + row as f32 * BUTTON_SPACING,
+            BUTTON_WIDTH,
+        )
+    }
+}
+
+pub fn main_menu(settings: &mut Settings, colours: &Colours) -> Option<Transition> {
+    let ui = Ui::new(settings.scale, colours);
+    ui.centred_text("Minesweeper", 36.0, TITLE_FONT_SIZE);
+
+    for (row, difficulty) in Difficulty::STANDARD.into_iter().enumerate() {
+        if ui.button(difficulty.name(), row) {
+            return Some(Transition::NewGame(difficulty));
+        }
+    }
+
+    if ui.button("Daily", 3) {
+        return Some(Transition::Daily);
+    }
+
+    if ui.button("Pattern trainer", 4) {
+        return Some(Transition::Trainer);
+    }
+
+    if ui.button("Tutorial", 5) {
+        return Some(Transition::Tutorial);
+    }
+
+    if ui.button("Custom...", 6) {
+        return Some(Transition::To(State::CustomGame));
+    }
+
+    if ui.button(&format!("Mode: {}", settings.mode.name()), 7) {
+        settings.mode = settings.mode.next();
+    }
+
+    if ui.button(&format!("Board: {}", settings.variant.topology.name()), 8) {
+        settings.variant.topology = settings.variant.topology.next();
+    }
+
+    let mines_per_tile = settings.variant.mines_per_tile;
+    if ui.button(&format!("Mines per tile: {mines_per_tile}"), 9) {
+        settings.variant.next_mines_per_tile();
+    }
+
+    if ui.button(&format!("Clues: {}", settings.variant.clues.name()), 10) {
+        settings.variant.clues = settings.variant.clues.next();
+    }
+
+    if ui.button(&format!("Fog: {}", settings.fog.name()), 11) {
+        settings.fog = settings.fog.next();
+    }
+
+    if ui.button("Stats", 12) {
+        return Some(Transition::To(State::Stats));
+    }
+
+    if ui.button("Leaderboards", 13) {
+        return Some(Transition::To(State::Leaderboards(Difficulty::Expert)));
+    }
+
+    if ui.button("Settings", 14) {
+        return Some(Transition::To(State::Settings));
+    }
+
+    if ui.button("Quit", 15) {
+        return Some(Transition::Quit);
+    }
+
+    None
+}
+
+pub fn settings_menu(settings: &mut Settings, themes: &mut ThemeSelection) -> Option<Transition> {
+    let colours = themes.current().colours;
+    let ui = Ui::new(settings.scale, &colours);
+    ui.centred_text("Settings", 36.0, TITLE_FONT_SIZE);
+
+    if ui.button(&format!("Theme: {}", themes.current().name), 0) {
+        themes.next();
+    }
+
+    if ui.button(&format!("Palette: {}", settings.palette.name()), 1) {
+        settings.palette = settings.palette.next();
+    }
+
+    let number_style = match settings.number_style {
+        NumberStyle::Textures => "Textures",
+        NumberStyle::Glyphs => "Glyphs",
+    };
+    if ui.button(&format!("Numbers: {number_style}"), 2) {
+        settings.number_style = match settings.number_style {
+            NumberStyle::Textures => NumberStyle::Glyphs,
+            NumberStyle::Glyphs => NumberStyle::Textures,
+        };
+    }
+
+    if ui.button(&format!("Scale: {}x", settings.scale), 3) {
+        settings.next_scale();
+    }
+
+    let question_marks = if settings.question_marks { "On" } else { "Off" };
+    if ui.button(&format!("Question marks: {question_marks}"), 4) {
+        settings.question_marks = !settings.question_marks;
+    }
+
+    if ui.button(&format!("Chording: {}", settings.chord_style.name()), 5) {
+        settings.chord_style = settings.chord_style.next();
+    }
+
+    if ui.button(&format!("Timer: {}", settings.timer_precision.name()), 6) {
+        settings.timer_precision = settings.timer_precision.next();
+    }
+
+    if ui.button(&format!("Solver: {}", settings.solver.name()), 7) {
+        settings.solver = settings.solver.next();
+    }
+
+    if ui.button(&format!("Wrong flags: {}", settings.wrong_flags.name()), 8) {
+        settings.wrong_flags = settings.wrong_flags.next();
+    }
+
+    let ghost_edges = if settings.ghost_edges { "On" } else { "Off" };
+    if ui.button(&format!("Ghost edges: {ghost_edges}"), 9) {
+        settings.ghost_edges = !settings.ghost_edges;
+    }
+
+    if let Some(author) = &themes.current().author {
+        ui.centred_text(
+            &format!("Theme by {author}"),
+            FIRST_ROW_Y + 11.0 * BUTTON_SPACING + 15.0,
+            FONT_SIZE,
+        );
+    }
+
+    if ui.button("Back", 10) || is_key_pressed(KeyCode::Escape) {
+        return Some(Transition::To(State::Menu));
+    }
+
+    None
+}
+
+pub fn custom_game_menu(settings: &mut Settings, colours: &Colours) -> Option<Transition> {
+    let ui = Ui::new(settings.scale, colours);
+    ui.centred_text("Custom", 36.0, TITLE_FONT_SIZE);
+
+    let (mut width, mut height, mut mines) = (
+        settings.custom.width(),
+        settings.custom.height(),
+        settings.custom.mines(),
+    );
+
+    // shift steps by ten
+    let step = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+        10
+    } else {
+        1
+    };
+
+    for (row, (name, value, max)) in [
+        ("Width", &mut width, MAX_CUSTOM_WIDTH),
+        ("Height", &mut height, MAX_CUSTOM_HEIGHT),
+        ("Mines", &mut mines, usize::MAX),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let y = FIRST_ROW_Y + row as f32 * BUTTON_SPACING;
+        ui.text(&format!("{name}: {value}"), 60.0, y + 15.0);
+
+        if ui.button_at("-", 180.0, y, BUTTON_HEIGHT) {
+            *value = value.saturating_sub(step).max(1);
+        }
+
+        if ui.button_at("+", 210.0, y, BUTTON_HEIGHT) {
+            *value = value.saturating_add(step).min(max);
+        }
+    }
+
+    settings.custom = Difficulty::custom(width, height, mines, settings.variant);
+
+    if ui.button("Start", 4) {
+        return Some(Transition::NewGame(settings.custom));
+    }
+
+    if ui.button("Back", 8) || is_key_pressed(KeyCode::Escape) {
+        return Some(Transition::To(State::Menu));
+    }
+
+    None
+}
+
+pub fn stats_menu(settings: &Settings, stats: &Stats, colours: &Colours) -> Option<Transition> {
+    let ui = Ui::new(settings.scale, colours);
+    ui.centred_text("Stats", 36.0, TITLE_FONT_SIZE);
+
+    for (row, difficulty) in Difficulty::STANDARD
+        .into_iter()
+        .chain([settings.custom])
+        .enumerate()
+    {
+        let difficulty_stats = stats.get(difficulty);
+        let y = FIRST_ROW_Y + 15.0 + row as f32 * 2.0 * BUTTON_SPACING;
+        let best = difficulty_stats
+            .leaderboard
+            .first()
+            .map_or("-".to_owned(), |record| {
+                settings.timer_precision.format(record.seconds)
+            });
+
+        ui.text(difficulty.name(), 30.0, y);
+        ui.text(
+            &format!(
+                "{} opened  {} bad flags",
+                difficulty_stats.tiles_opened, difficulty_stats.wrong_flags
+            ),
+            130.0,
+            y,
+        );
+        ui.text(
+            &format!(
+                "played {}  won {} ({:.0}%)  best {best}",
+                difficulty_stats.played,
+                difficulty_stats.won,
+                difficulty_stats.win_percentage()
+            ),
+            40.0,
+            y + 18.0,
+        );
+    }
+
+    let daily = stats.daily();
+    let y = FIRST_ROW_Y + 15.0 + 4.0 * 2.0 * BUTTON_SPACING;
+    ui.text("Daily", 30.0, y);
+    ui.text(
+        &format!("streak {}", stats.daily_streak(daily::today())),
+        130.0,
+        y,
+    );
+    ui.text(
+        &format!(
+            "played {}  won {}",
+            daily.len(),
+            daily.iter().filter(|result| result.won).count()
+        ),
+        40.0,
+        y + 18.0,
+    );
+
+    if ui.button("Back", 10) || is_key_pressed(KeyCode::Escape) {
+        return Some(Transition::To(State::Menu));
+    }
+
+    None
+}
+
+pub fn leaderboards_menu(
+    difficulty: Difficulty,
+    settings: &Settings,
+    stats: &Stats,
+    colours: &Colours,
+) -> Option<Transition> {
+    let ui = Ui::new(settings.scale, colours);
+    ui.centred_text("Leaderboards", 36.0, TITLE_FONT_SIZE);
+
+    let index = Difficulty::STANDARD
+        .iter()
+        .position(|&d| d == difficulty)
+        .unwrap_or(0);
+
+    if ui.button(&format!("< {} >", difficulty.name()), 0) {
+        let next = Difficulty::STANDARD[(index + 1) % Difficulty::STANDARD.len()];
+        return Some(Transition::To(State::Leaderboards(next)));
+    }
+
+    let leaderboard = &stats.get(difficulty).leaderboard;
+
+    if leaderboard.is_empty() {
+        ui.centred_text("No wins yet", FIRST_ROW_Y + 50.0, FONT_SIZE);
+    }
+
+    for (position, record) in leaderboard.iter().enumerate() {
+        let y = FIRST_ROW_Y + 40.0 + position as f32 * 16.0;
+        ui.text(&format!("{}.", position + 1), 70.0, y);
+        ui.text(&settings.timer_precision.format(record.seconds), 100.0, y);
+        ui.text(&format_date(record.date), 170.0, y);
+    }
+
+    if ui.button("Back", 9) || is_key_pressed(KeyCode::Escape) {
+        return Some(Transition::To(State::Menu));
+    }
+
+    None
+}
@@ -0,0 +1,138 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Colours for the parts of the board that carry meaning: the neighbour counts, flags and the
+/// mine that lost the game.
+#[derive(PartialEq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Palette {
+    #[default]
+    Classic,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    /// Everything in black, relying on the shape of the glyphs, flags and the lost mine marker.
+    Monochrome,
+}
+
+/// How the neighbour counts are drawn. Only glyphs can be recoloured, so any palette other
+/// than the classic one always draws glyphs.
+#[derive(PartialEq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NumberStyle {
+    #[default]
+    Textures,
+    Glyphs,
+}
+
+const fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0)
+}
+
+const CLASSIC_NUMBERS: [Color; 8] = [
+    rgb(0, 0, 255),
+    rgb(0, 170, 0),
+    rgb(255, 0, 0),
+    rgb(0, 0, 136),
+    rgb(136, 0, 0),
+    rgb(0, 170, 170),
+    rgb(136, 136, 136),
+    rgb(0, 0, 0),
+];
+
+// red-green deficiencies keep the blue/yellow axis and lightness
+const DEUTERANOPIA_NUMBERS: [Color; 8] = [
+    rgb(0, 114, 178),
+    rgb(230, 159, 0),
+    rgb(120, 40, 140),
+    rgb(0, 40, 100),
+    rgb(140, 80, 0),
+    rgb(86, 180, 233),
+    rgb(0, 0, 0),
+    rgb(110, 110, 110),
+];
+
+// reds look darker to protanopes, so the warm colours are pushed towards yellow
+const PROTANOPIA_NUMBERS: [Color; 8] = [
+    rgb(0, 114, 178),
+    rgb(220, 190, 0),
+    rgb(90, 30, 130),
+    rgb(0, 40, 100),
+    rgb(150, 110, 0),
+    rgb(86, 180, 233),
+    rgb(0, 0, 0),
+    rgb(110, 110, 110),
+];
+
+// blue-yellow deficiency keeps the red/teal axis
+const TRITANOPIA_NUMBERS: [Color; 8] = [
+    rgb(0, 140, 140),
+    rgb(220, 20, 60),
+    rgb(110, 0, 40),
+    rgb(0, 80, 80),
+    rgb(255, 110, 150),
+    rgb(70, 70, 70),
+    rgb(0, 0, 0),
+    rgb(130, 130, 130),
+];
+
+impl Palette {
+    pub const ALL: [Palette; 5] = [
+        Palette::Classic,
+        Palette::Deuteranopia,
+        Palette::Protanopia,
+        Palette::Tritanopia,
+        Palette::Monochrome,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Classic => "Classic",
+            Palette::Deuteranopia => "Deuteranopia",
+            Palette::Protanopia => "Protanopia",
+            Palette::Tritanopia => "Tritanopia",
+            Palette::Monochrome => "Monochrome",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Palette::ALL.iter().position(|&p| p == self).unwrap();
+        Palette::ALL[(index + 1) % Palette::ALL.len()]
+    }
+
+    pub fn number_colour(self, neighbour_mines: u8) -> Color {
+        let numbers = match self {
+            Palette::Classic => &CLASSIC_NUMBERS,
+            Palette::Deuteranopia => &DEUTERANOPIA_NUMBERS,
+            Palette::Protanopia => &PROTANOPIA_NUMBERS,
+            Palette::Tritanopia => &TRITANOPIA_NUMBERS,
+            Palette::Monochrome => return BLACK,
+        };
+
+        // tiles holding several mines can have numbers past 8, which share its colour, and
+        // lying clues can show 0, which shares the colour of 1
+        numbers[(neighbour_mines as usize).clamp(1, numbers.len()) - 1]
+    }
+
+    pub fn flag_colour(self) -> Color {
+        match self {
+            Palette::Classic => RED,
+            Palette::Deuteranopia | Palette::Protanopia => rgb(230, 159, 0),
+            Palette::Tritanopia => rgb(220, 20, 60),
+            Palette::Monochrome => BLACK,
+        }
+    }
+
+    /// Background of the mine that was clicked to lose the game.
+    pub fn exploded_colour(self) -> Color {
+        match self {
+            Palette::Classic | Palette::Tritanopia => RED,
+            Palette::Deuteranopia | Palette::Protanopia => rgb(240, 228, 66),
+            Palette::Monochrome => WHITE,
+        }
+    }
+
+    pub fn uses_glyphs(self, number_style: NumberStyle) -> bool {
+        self != Palette::Classic || number_style == NumberStyle::Glyphs
+    }
+}
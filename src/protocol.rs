@@ -0,0 +1,409 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    process::{Command, Stdio},
+};
+
+use eyre::{bail, eyre, Result, WrapErr};
+use serde::Serialize;
+
+use crate::{
+    board_layout,
+    grid::{Clues, Topology},
+    settings::Settings,
+    Action, Board, State, TileState,
+};
+
+/// What the game sends a bot, one JSON object per line.
+///
+/// ```text
+/// {"type":"board","width":9,"height":9,"mines":10,...,"rows":["#########",...]}
+/// reveal 4 4
+/// {"type":"result","move":"reveal 4 4","ok":true,"state":"playing","revealed":[...],...}
+/// ```
+///
+/// Each row of the board has one character a tile: `#` hidden, `F` flagged, `?` question
+/// marked, a digit for a revealed number, with letters for numbers past 9, `~` for a number
+/// lost in the fog, and `*` for a mine once the game is lost. Columns are `x` and rows `y`.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Message {
+    /// Sent when a game starts, and again whenever the bot asks with `board`.
+    Board {
+        /// Places the mines, along with the first reveal. Each new game counts up by one.
+        seed: u64,
+        width: usize,
+        height: usize,
+        mines: usize,
+        topology: Topology,
+        mines_per_tile: u8,
+        clues: Clues,
+        state: GameState,
+        rows: Vec<String>,
+    },
+    /// Sent after every move, with the tiles it revealed in the order they were opened.
+    Result {
+        #[serde(rename = "move")]
+        action: String,
+        ok: bool,
+        state: GameState,
+        seconds: f32,
+        revealed: Vec<Revealed>,
+        rows: Vec<String>,
+    },
+    /// Sent for lines that aren't a move, or moves that make no sense on the board.
+    Error { line: String, error: String },
+}
+
+#[derive(Serialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum GameState {
+    /// Waiting for the first reveal, which is always safe on a generated board.
+    New,
+    Playing,
+    Won,
+    Lost,
+}
+
+#[derive(Serialize)]
+pub struct Revealed {
+    x: usize,
+    y: usize,
+    tile: char,
+}
+
+/// A game for a bot to play, with no window. Reads moves as text and answers with messages.
+pub struct Engine {
+    board: Board,
+    settings: Settings,
+    /// How long the last game took, once it is over.
+    finished_seconds: f32,
+}
+
+impl Engine {
+    /// Question marks are turned off, so flagging a tile twice always takes the flag off.
+    pub fn new(board: Board, mut settings: Settings) -> Self {
+        settings.question_marks = false;
+
+        let mut engine = Self {
+            board,
+            settings,
+            finished_seconds: 0.0,
+        };
+        engine.look_at(0, 0);
+        engine
+    }
+
+    pub fn state(&self) -> GameState {
+        match self.board.state {
+            State::NewGame => GameState::New,
+            State::Won => GameState::Won,
+            State::Dead => GameState::Lost,
+            _ => GameState::Playing,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        matches!(self.state(), GameState::Won | GameState::Lost)
+    }
+
+    /// Seconds since the first click, stopping when the game ends.
+    pub fn seconds(&self) -> f32 {
+        match self.state() {
+            GameState::New => 0.0,
+            GameState::Playing => self.board.start.elapsed().as_secs_f32(),
+            GameState::Won | GameState::Lost => self.finished_seconds,
+        }
+    }
+
+    pub fn board_message(&self) -> Message {
+        let board = &self.board;
+
+        Message::Board {
+            seed: board.seed,
+            width: board.width(),
+            height: board.height(),
+            mines: board.mines,
+            topology: board.topology,
+            mines_per_tile: board.mines_per_tile,
+            clues: board.clues,
+            state: self.state(),
+            rows: self.rows(),
+        }
+    }
+
+    /// Plays one line from the bot: a move, `board` to be sent the board again, or `new` to
+    /// start another game on the next seed once this one is over.
+    pub fn play(&mut self, line: &str) -> Message {
+        let line = line.trim();
+
+        match line {
+            "board" => return self.board_message(),
+            "new" if self.finished() => {
+                self.board = self.board.reseeded(self.board.seed.wrapping_add(1));
+                self.look_at(0, 0);
+                return self.board_message();
+            }
+            _ => {}
+        }
+
+        let error = |error: String| Message::Error {
+            line: line.to_owned(),
+            error,
+        };
+
+        let action = match line.parse() {
+            Ok(Action::Solve) => return error("the built in bot isn't available".to_owned()),
+            Ok(action) => action,
+            Err(report) => return error(format!("{report}")),
+        };
+
+        if let Action::Reveal(col, row) | Action::Flag(col, row) | Action::Chord(col, row) = action
+        {
+            if col >= self.board.width() || row >= self.board.height() {
+                return error(format!("{action} is off the board"));
+            }
+        }
+
+        let was_revealed = self
+            .board
+            .tiles
+            .map(|tile| tile.state == TileState::Revealed);
+        let was_finished = self.finished();
+        let flagged = self.board.number_flagged;
+
+        self.board.apply(action, &self.settings);
+        self.board.settle();
+
+        if !was_finished && self.finished() {
+            self.finished_seconds = self.board.start.elapsed().as_secs_f32();
+        }
+
+        if let Action::Reveal(col, row) | Action::Flag(col, row) | Action::Chord(col, row) = action
+        {
+            self.look_at(col, row);
+        }
+
+        // opening up empty space reveals nearest first, so the animation order is kept
+        let opened = std::mem::take(&mut self.board.opening);
+        let mut revealed = opened
+            .into_iter()
+            .map(|(opened, _)| (opened.col, opened.row))
+            .filter(|&tile| !was_revealed[tile])
+            .map(|(col, row)| Revealed {
+                x: col,
+                y: row,
+                tile: self.tile(col, row),
+            })
+            .collect::<Vec<_>>();
+
+        // losing reveals every mine without opening them
+        for (col, row) in self.board.tiles.positions() {
+            let already = revealed.iter().any(|tile| (tile.x, tile.y) == (col, row));
+            if self.board.revealed(col, row) && !was_revealed[(col, row)] && !already {
+                revealed.push(Revealed {
+                    x: col,
+                    y: row,
+                    tile: self.tile(col, row),
+                });
+            }
+        }
+
+        Message::Result {
+            action: action.to_string(),
+            // moves that change nothing, such as revealing an open tile, still make the history
+            ok: !revealed.is_empty() || self.board.number_flagged != flagged,
+            state: self.state(),
+            seconds: self.seconds(),
+            revealed,
+            rows: self.rows(),
+        }
+    }
+
+    /// Works out the fog with the last tile played standing in for the mouse.
+    fn look_at(&mut self, col: usize, row: usize) {
+        let layout = board_layout(&self.board, &self.settings);
+        let (x, y) = layout.tile_centre(col, row);
+        self.board.look(self.settings.fog, &layout, x, y);
+    }
+
+    fn rows(&self) -> Vec<String> {
+        (0..self.board.height())
+            .map(|row| {
+                (0..self.board.width())
+                    .map(|col| self.tile(col, row))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn tile(&self, col: usize, row: usize) -> char {
+        let board = &self.board;
+        let tile = &board.tiles[(col, row)];
+
+        match tile.state {
+            TileState::Hidden => '#',
+            TileState::Flagged => 'F',
+            TileState::Question => '?',
+            TileState::Revealed if board.mine(col, row) => '*',
+            TileState::Revealed if !board.in_sight[(col, row)] => '~',
+            TileState::Revealed => char::from_digit(tile.clue as u32, 36).unwrap_or('+'),
+        }
+    }
+}
+
+/// Plays games against a bot reading messages from `moves` and writing them to `messages`,
+/// until the bot closes its end. Returns the results of the games it finished.
+pub fn serve(
+    engine: &mut Engine,
+    moves: impl BufRead,
+    mut messages: impl Write,
+) -> Result<Vec<(GameState, f32)>> {
+    let mut results = vec![];
+
+    send(&mut messages, &engine.board_message())?;
+
+    for line in moves.lines() {
+        let line = line.wrap_err("reading a move")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let finished = engine.finished();
+        let message = engine.play(&line);
+        send(&mut messages, &message)?;
+
+        if !finished && engine.finished() {
+            results.push((engine.state(), engine.seconds()));
+        }
+    }
+
+    Ok(results)
+}
+
+pub fn send(messages: &mut impl Write, message: &impl Serialize) -> Result<()> {
+    serde_json::to_writer(&mut *messages, message)?;
+    writeln!(messages)?;
+    messages.flush().wrap_err("sending a message")
+}
+
+/// Plays over stdin and stdout, for a bot on the other end of a pipe or typed by hand.
+pub fn run_stdio(board: Board, settings: Settings) -> Result<()> {
+    let mut engine = Engine::new(board, settings);
+    serve(&mut engine, io::stdin().lock(), io::stdout().lock())?;
+    Ok(())
+}
+
+/// Runs a bot as a child process, given as a program and its arguments separated by spaces,
+/// and plays against it over its stdin and stdout. Its stderr is left on the terminal.
+pub fn run_child(board: Board, settings: Settings, command: &str) -> Result<()> {
+    let mut words = command.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| eyre!("the bot command is empty"))?;
+
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .wrap_err_with(|| format!("starting {program}"))?;
+
+    let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        bail!("no pipes to {program}");
+    };
+
+    let mut engine = Engine::new(board, settings);
+    let results = serve(&mut engine, BufReader::new(stdout), stdin);
+
+    // the bot may still be running if we stopped early
+    let _ = child.kill();
+    child
+        .wait()
+        .wrap_err_with(|| format!("waiting for {program}"))?;
+
+    for (state, seconds) in results? {
+        match state {
+            GameState::Won => println!("won in {seconds:.3}s"),
+            _ => println!("lost after {seconds:.3}s"),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        replay::{layout_board, parse_rows},
+        settings::Variant,
+    };
+
+    fn engine(rows: &[&str]) -> Engine {
+        let (layout, _) = parse_rows(rows).unwrap();
+        let board = layout_board(&layout, None, Variant::default());
+        Engine::new(board, Settings::default())
+    }
+
+    fn sent(message: Message) -> Value {
+        serde_json::to_value(message).unwrap()
+    }
+
+    #[test]
+    fn moves_are_answered_with_what_they_revealed() {
+        let mut engine = engine(&["*..*", "....", "...."]);
+
+        let board = sent(engine.board_message());
+        assert_eq!(board["type"], "board");
+        assert_eq!(board["state"], "new");
+        assert_eq!(board["rows"], json!(["####", "####", "####"]));
+
+        let result = sent(engine.play("reveal 0 2"));
+        assert_eq!(result["type"], "result");
+        assert_eq!(result["move"], "reveal 0 2");
+        assert_eq!(result["ok"], true);
+        assert_eq!(result["state"], "playing");
+        assert_eq!(result["revealed"][0], json!({"x": 0, "y": 2, "tile": "0"}));
+        assert_eq!(result["revealed"].as_array().unwrap().len(), 8);
+        assert_eq!(result["rows"], json!(["####", "1111", "0000"]));
+
+        let result = sent(engine.play("flag 0 0"));
+        assert_eq!(result["ok"], true);
+        assert_eq!(result["revealed"], json!([]));
+        assert_eq!(result["rows"], json!(["F###", "1111", "0000"]));
+
+        engine.play("reveal 1 0");
+        let result = sent(engine.play("reveal 2 0"));
+        assert_eq!(result["state"], "won");
+        assert!(engine.finished());
+    }
+
+    #[test]
+    fn lines_that_are_not_moves_get_an_error() {
+        let mut engine = engine(&["*..", "...", "..."]);
+
+        for line in ["dance", "reveal 3 0", "flag 1", "new"] {
+            let error = sent(engine.play(line));
+            assert_eq!(error["type"], "error", "{line:?}");
+            assert_eq!(error["line"], line);
+        }
+
+        assert_eq!(sent(engine.play(" board ")), sent(engine.board_message()));
+        assert!(engine.state() == GameState::New);
+    }
+
+    #[test]
+    fn moves_that_change_nothing_are_not_ok() {
+        let mut engine = engine(&["*..*", "....", "...."]);
+
+        assert_eq!(sent(engine.play("reveal 0 2"))["ok"], true);
+        assert_eq!(sent(engine.play("reveal 0 2"))["ok"], false);
+        // the 1 has no flag next to it yet
+        assert_eq!(sent(engine.play("chord 0 1"))["ok"], false);
+
+        assert_eq!(sent(engine.play("flag 0 0"))["ok"], true);
+        assert_eq!(sent(engine.play("flag 0 0"))["ok"], true);
+    }
+}
@@ -0,0 +1,378 @@
+use std::{fs, path::Path, str::FromStr};
+
+use eyre::{bail, ensure, Result, WrapErr};
+use macroquad::prelude::*;
+
+use crate::{
+    bitboard::BitGame,
+    grid::Grid,
+    race::draw_banner,
+    replay::{layout_board, parse_rows},
+    settings::{Settings, Variant},
+    theme::Theme,
+    versus::draw_status,
+    Board, Layout, State, TileState,
+};
+
+/// A position made to be solved by logic alone, starting with some of its safe tiles open.
+pub struct Puzzle {
+    pub title: String,
+    pub author: Option<String>,
+    pub difficulty: Option<String>,
+    layout: Grid<u8>,
+    given: Grid<bool>,
+}
+
+impl Puzzle {
+    pub fn board(&self) -> Board {
+        layout_board(&self.layout, Some(&self.given), Variant::default())
+    }
+
+    /// Checks that the puzzle can be solved from where it starts without a guess. The solver's
+    /// simplest rules only make moves that follow from a single number, so when they clear the
+    /// board every move was forced and the puzzle has exactly one solution.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.layout.iter().all(|&mines| mines <= 1),
+            "a tile holds more than one mine"
+        );
+        ensure!(self.given.iter().any(|&given| given), "no tiles start open");
+
+        let board = self.board();
+        ensure!(board.revealed_safe < board.safe_tiles, "it starts solved");
+
+        let mut game = BitGame::from_board(&board);
+        ensure!(
+            game.solve(),
+            "the solver gets stuck here, so it may need a guess:\n{}",
+            position(&game.to_board())
+        );
+
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        let mut description = self.title.clone();
+
+        if let Some(author) = &self.author {
+            description.push_str(&format!(" by {author}"));
+        }
+
+        if let Some(difficulty) = &self.difficulty {
+            description.push_str(&format!(" ({difficulty})"));
+        }
+
+        description
+    }
+}
+
+/// A board as rows of text, with `#` for a hidden tile, `F` for a flag and the number on each
+/// open tile.
+fn position(board: &Board) -> String {
+    (0..board.height())
+        .map(|row| {
+            (0..board.width())
+                .map(|col| match board.tiles[(col, row)].state {
+                    TileState::Flagged => 'F',
+                    TileState::Revealed => char::from(b'0' + board.clue(col, row)),
+                    _ => '#',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Puzzles kept together in a text file, each starting with a `puzzle` line giving its title.
+///
+/// ```text
+/// puzzle Left over
+/// author Freddy
+/// difficulty easy
+/// row -----
+/// row .*...
+/// ```
+///
+/// Rows use `*` for a mine, `.` for a safe tile that starts hidden and `-` for one that starts
+/// open. The author and difficulty can be left out.
+pub struct Pack {
+    pub puzzles: Vec<Puzzle>,
+}
+
+impl Pack {
+    pub fn load(path: &Path) -> Result<Self> {
+        fs::read_to_string(path)
+            .wrap_err_with(|| format!("reading {}", path.display()))?
+            .parse()
+            .wrap_err_with(|| format!("parsing {}", path.display()))
+    }
+
+    /// Validates every puzzle, printing how each one did, and fails if any of them didn't.
+    pub fn check(&self) -> Result<()> {
+        let mut failed = 0;
+
+        for (number, puzzle) in self.puzzles.iter().enumerate() {
+            match puzzle.validate() {
+                Ok(()) => println!("{}. {}: ok", number + 1, puzzle.title),
+                Err(error) => {
+                    println!("{}. {}: {error}", number + 1, puzzle.title);
+                    failed += 1;
+                }
+            }
+        }
+
+        ensure!(
+            failed == 0,
+            "{failed} of {} puzzles failed",
+            self.puzzles.len()
+        );
+        Ok(())
+    }
+}
+
+impl FromStr for Pack {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut drafts: Vec<Draft> = vec![];
+
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            let context = || format!("line {}", number + 1);
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let rest = rest.trim();
+
+            if keyword == "puzzle" {
+                ensure!(!rest.is_empty(), "{}: the puzzle has no title", context());
+                drafts.push(Draft {
+                    title: rest.to_owned(),
+                    author: None,
+                    difficulty: None,
+                    rows: vec![],
+                });
+                continue;
+            }
+
+            let Some(draft) = drafts.last_mut() else {
+                bail!("{}: expected a puzzle line first", context());
+            };
+
+            match keyword {
+                "author" => draft.author = Some(rest.to_owned()),
+                "difficulty" => draft.difficulty = Some(rest.to_owned()),
+                "row" => draft.rows.push(rest),
+                _ => bail!("{}: unexpected {line:?}", context()),
+            }
+        }
+
+        let puzzles = drafts
+            .into_iter()
+            .map(|draft| {
+                let (layout, given) = parse_rows(&draft.rows)
+                    .wrap_err_with(|| format!("puzzle {:?}", draft.title))?;
+
+                Ok(Puzzle {
+                    title: draft.title,
+                    author: draft.author,
+                    difficulty: draft.difficulty,
+                    layout,
+                    given,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        ensure!(!puzzles.is_empty(), "the pack has no puzzles");
+        Ok(Self { puzzles })
+    }
+}
+
+/// A puzzle as far as it has been read, before its rows are put together.
+struct Draft<'a> {
+    title: String,
+    author: Option<String>,
+    difficulty: Option<String>,
+    rows: Vec<&'a str>,
+}
+
+/// Plays through a pack, one puzzle at a time.
+pub struct Puzzles {
+    pack: Pack,
+    current: usize,
+    solved: Vec<bool>,
+}
+
+impl Puzzles {
+    pub fn new(pack: Pack) -> Self {
+        let solved = vec![false; pack.puzzles.len()];
+        let puzzles = Self {
+            pack,
+            current: 0,
+            solved,
+        };
+        puzzles.announce();
+        puzzles
+    }
+
+    /// The board for the puzzle being played.
+    pub fn board(&self) -> Board {
+        self.pack.puzzles[self.current].board()
+    }
+
+    /// Moves to the next or previous puzzle on the arrow keys, and draws which puzzle this is
+    /// under the board.
+    pub fn frame(
+        &mut self,
+        board: &mut Board,
+        layout: &Layout,
+        settings: &Settings,
+        theme: &Theme,
+    ) {
+        let count = self.pack.puzzles.len();
+        let step = if is_key_pressed(KeyCode::Right) {
+            Some(1)
+        } else if is_key_pressed(KeyCode::Left) {
+            Some(count - 1)
+        } else {
+            None
+        };
+
+        if let Some(step) = step {
+            self.current = (self.current + step) % count;
+            *board = self.board();
+            self.announce();
+        }
+
+        if board.state == State::Won {
+            self.solved[self.current] = true;
+        }
+
+        let solved = self.solved.iter().filter(|&&solved| solved).count();
+        let status = format!(
+            "{}/{count} {}",
+            self.current + 1,
+            self.pack.puzzles[self.current].title
+        );
+        let bar = settings.palette.flag_colour();
+        draw_status(
+            &status,
+            solved as f32 / count as f32,
+            bar,
+            layout,
+            &theme.colours,
+        );
+
+        let result = match board.state {
+            State::Won => Some("Solved"),
+            State::Dead => Some("Mistake"),
+            _ => None,
+        };
+
+        if let Some(result) = result {
+            draw_banner(result, layout.window_width / 2.0, layout, &theme.colours);
+        }
+    }
+
+    fn announce(&self) {
+        println!(
+            "puzzle {}/{}: {}",
+            self.current + 1,
+            self.pack.puzzles.len(),
+            self.pack.puzzles[self.current].describe()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEFT_OVER: &str = "
+        puzzle Left over
+        author Freddy
+        difficulty easy
+        row -----
+        row .*...
+    ";
+
+    fn puzzle(rows: &str) -> Puzzle {
+        format!("puzzle Test\n{rows}")
+            .parse::<Pack>()
+            .unwrap()
+            .puzzles
+            .remove(0)
+    }
+
+    #[test]
+    fn packs_are_read_puzzle_by_puzzle() {
+        let pack = format!("# two puzzles\n{LEFT_OVER}\npuzzle Plain\nrow -*\n")
+            .parse::<Pack>()
+            .unwrap();
+        assert_eq!(pack.puzzles.len(), 2);
+
+        let [left, plain] = &pack.puzzles[..] else {
+            unreachable!()
+        };
+        assert_eq!(left.describe(), "Left over by Freddy (easy)");
+        assert_eq!((left.layout.width(), left.layout.height()), (5, 2));
+        assert_eq!(left.layout[(1, 1)], 1);
+        assert!(left.given[(4, 0)] && !left.given[(4, 1)]);
+        assert_eq!(plain.describe(), "Plain");
+    }
+
+    #[test]
+    fn broken_packs_are_refused() {
+        for text in [
+            "",
+            "# nothing but comments",
+            "row ...",
+            "puzzle\nrow ...",
+            "puzzle No rows",
+            "puzzle Ragged\nrow ...\nrow ..",
+            "puzzle Sized\nsize 3 3 1\nrow ...",
+            "puzzle Letters\nrow .x.",
+        ] {
+            assert!(text.parse::<Pack>().is_err(), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn puzzles_must_be_solvable_without_guessing() {
+        LEFT_OVER.parse::<Pack>().unwrap().check().unwrap();
+
+        for rows in [
+            // nothing to start from
+            "row ...\nrow ..*",
+            // nothing left to do
+            "row -*",
+            // either tile could be the mine
+            "row --\nrow .*",
+            // puzzles are played with one mine a tile
+            "row -2",
+        ] {
+            assert!(puzzle(rows).validate().is_err(), "{rows:?}");
+        }
+
+        let stuck = puzzle("row ---\nrow ...\nrow .*.\nrow ...\nrow -*-")
+            .validate()
+            .unwrap_err();
+        assert!(
+            stuck.to_string().ends_with(":\n000\n111\n###\n###\n1#1"),
+            "{stuck}"
+        );
+    }
+
+    #[test]
+    fn the_starter_pack_is_all_solvable() {
+        let pack = include_str!("../puzzles/starter.txt")
+            .parse::<Pack>()
+            .unwrap();
+        for puzzle in &pack.puzzles {
+            puzzle.validate().wrap_err(puzzle.title.clone()).unwrap();
+        }
+    }
+}
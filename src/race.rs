@@ -0,0 +1,194 @@
+use macroquad::prelude::*;
+
+use crate::{
+    draw_bevel, draw_counters, draw_frame, draw_smiley, draw_tiles, outline_tile_shape,
+    settings::{ChordStyle, Mode, Settings},
+    smiley_for,
+    theme::{Colours, Theme},
+    Action, Board, Layout, State,
+};
+
+const BANNER_FONT_SIZE: f32 = 20.0;
+const BANNER_PADDING: f32 = 8.0;
+const CURSOR_THICKNESS: f32 = 2.0;
+
+#[derive(PartialEq, Clone, Copy)]
+enum Side {
+    Player,
+    Rival,
+}
+
+/// The second board in a race, drawn to the right of the player's. It has the same mines, is
+/// opened by the player's first click and is then played by the bot or on the keyboard. The
+/// first side to clear its board wins, and a side that hits a mine is out of the race.
+pub struct Rival {
+    mode: Mode,
+    board: Board,
+    /// The tile the keyboard is on. The bot reads through the fog from here too.
+    cursor: (usize, usize),
+    winner: Option<Side>,
+}
+
+impl Rival {
+    /// A rival for the player's board, if `mode` is a race.
+    pub fn new(player: &Board, mode: Mode) -> Option<Self> {
+        mode.racing().then(|| Self {
+            mode,
+            board: player.reseeded(player.seed),
+            cursor: (player.width() / 2, player.height() / 2),
+            winner: None,
+        })
+    }
+
+    /// Plays and draws the rival's board beside the player's, which is drawn with `layout`.
+    pub fn frame(&mut self, player: &Board, layout: &Layout, settings: &Settings, theme: &Theme) {
+        // the player started again, on the same board or a new one
+        let restarted = player.state == State::NewGame && self.board.state != State::NewGame;
+        if restarted || player.seed != self.board.seed {
+            self.board = player.reseeded(player.seed);
+            self.winner = None;
+        }
+
+        if self.board.state == State::NewGame {
+            if let Some(&(_, first @ Action::Reveal(..))) = player.history.first() {
+                self.board.apply(first, settings);
+            }
+        }
+
+        let layout = layout.beside();
+
+        if self.winner.is_none() && self.board.state == State::Playing {
+            match self.mode {
+                Mode::RaceBot => {
+                    self.look(&layout, settings);
+                    self.board.computer_game(settings.solver);
+                }
+                _ => self.keys(settings),
+            }
+        }
+
+        self.board.settle();
+
+        if self.winner.is_none() {
+            if player.state == State::Won {
+                self.winner = Some(Side::Player);
+            } else if self.board.state == State::Won {
+                self.winner = Some(Side::Rival);
+            }
+        }
+
+        draw_frame(&layout, &theme.colours);
+        draw_counters(&self.board, &layout, &theme.textures);
+        draw_smiley(smiley_for(self.board.state, &theme.textures), &layout);
+
+        self.look(&layout, settings);
+        self.board.fade(get_frame_time());
+        draw_tiles(&mut self.board, &layout, theme, settings);
+
+        if self.mode == Mode::RaceKeyboard && self.board.state == State::Playing {
+            let (col, row) = self.cursor;
+            outline_tile_shape(
+                &layout,
+                col,
+                row,
+                CURSOR_THICKNESS * layout.scale,
+                settings.palette.flag_colour(),
+            );
+        }
+
+        if let Some(result) = self.result(player) {
+            draw_banner(result, layout.origin_x, &layout, &theme.colours);
+        }
+    }
+
+    fn look(&mut self, layout: &Layout, settings: &Settings) {
+        let (x, y) = layout.tile_centre(self.cursor.0, self.cursor.1);
+        self.board.look(settings.fog, layout, x, y);
+    }
+
+    /// Arrows move the cursor, space reveals or chords and F flags.
+    fn keys(&mut self, settings: &Settings) {
+        let (width, height) = (self.board.width(), self.board.height());
+        let (col, row) = self.cursor;
+
+        if is_key_pressed(KeyCode::Left) {
+            self.cursor.0 = (col + width - 1) % width;
+        }
+        if is_key_pressed(KeyCode::Right) {
+            self.cursor.0 = (col + 1) % width;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.cursor.1 = (row + height - 1) % height;
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.cursor.1 = (row + 1) % height;
+        }
+
+        let (col, row) = self.cursor;
+        let board = &self.board;
+
+        let action = if is_key_pressed(KeyCode::Space) {
+            if !board.revealed(col, row) {
+                (!board.flagged(col, row)).then_some(Action::Reveal(col, row))
+            } else {
+                (settings.chord_style != ChordStyle::Disabled && board.satisfied(col, row))
+                    .then_some(Action::Chord(col, row))
+            }
+        } else {
+            (is_key_pressed(KeyCode::F) && !board.revealed(col, row))
+                .then_some(Action::Flag(col, row))
+        };
+
+        if let Some(action) = action {
+            self.board.apply(action, settings);
+        }
+    }
+
+    /// What to announce once the race is over.
+    fn result(&self, player: &Board) -> Option<&'static str> {
+        match self.winner {
+            Some(Side::Player) if self.mode == Mode::RaceKeyboard => Some("Mouse wins"),
+            Some(Side::Player) => Some("You win"),
+            Some(Side::Rival) if self.mode == Mode::RaceKeyboard => Some("Keyboard wins"),
+            Some(Side::Rival) => Some("Bot wins"),
+            None if player.state == State::Dead && self.board.state == State::Dead => {
+                Some("Nobody wins")
+            }
+            None => None,
+        }
+    }
+}
+
+/// Announces the end of a race in a raised box halfway down the window, centred on `centre_x`.
+pub fn draw_banner(text: &str, centre_x: f32, layout: &Layout, colours: &Colours) {
+    let scale = layout.scale;
+    let font_size = (BANNER_FONT_SIZE * scale) as u16;
+    let dimensions = measure_text(text, None, font_size, 1.0);
+    let padding = BANNER_PADDING * scale;
+    let (width, height) = (
+        dimensions.width + 2.0 * padding,
+        dimensions.height + 2.0 * padding,
+    );
+    let (x, y) = (
+        centre_x - width / 2.0,
+        (layout.window_height - height) / 2.0,
+    );
+
+    draw_rectangle(x, y, width, height, colours.face);
+    draw_bevel(
+        x,
+        y,
+        width,
+        height,
+        2.0 * scale,
+        colours.highlight,
+        colours.shadow,
+    );
+    draw_text(
+        text,
+        x + padding,
+        y + padding + dimensions.offset_y,
+        font_size as f32,
+        colours.text,
+    );
+}
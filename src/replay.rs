@@ -0,0 +1,550 @@
+use std::{
+    collections::VecDeque,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Instant,
+};
+
+use eyre::{bail, ensure, eyre, Result, WrapErr};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    grid::{Clues, Grid, Topology},
+    settings::{ChordStyle, Fog, Settings, Solver, Variant, WrongFlags, MAX_MINES_PER_TILE},
+    stats::unix_now,
+    Action, Board,
+};
+
+const REPLAYS_DIR: &str = "replays";
+const REPLAY_EXTENSION: &str = "replay";
+const MINE: char = '*';
+const SAFE: char = '.';
+const OPEN: char = '-';
+
+/// A finished game: the board it was played on and every move made, kept as text so that
+/// replays can be read and edited by hand.
+///
+/// ```text
+/// size 30 16 99
+/// seed 1234
+/// 0.000 reveal 3 4
+/// 1.250 flag 5 6
+/// ```
+///
+/// Games on a board loaded from a file have `row` lines of mines instead of a seed, marking any
+/// tiles a puzzle started with open as they are in the file. Games on
+/// anything but a flat board of squares have a line naming the board, such as `torus`, and
+/// games where tiles can hold more than one mine have a `mines-per-tile` line. Games with other
+/// rules for the numbers have a `clues` line, such as `clues knight`. Settings that change
+/// what a move does have a line when they aren't the default, such as `question-marks on` or
+/// `wrong-flags clear`, so that the game plays back the same wherever it is watched.
+pub struct Replay {
+    width: usize,
+    height: usize,
+    mines: usize,
+    variant: Variant,
+    seed: u64,
+    /// Mines on each tile for games played on a layout loaded from a file.
+    layout: Option<Grid<u8>>,
+    /// Tiles open from the start, for puzzles.
+    given: Option<Grid<bool>>,
+    rules: Rules,
+    actions: Vec<(f32, Action)>,
+}
+
+/// The settings a game was played by that change what its moves do.
+#[derive(Default, PartialEq, Debug)]
+struct Rules {
+    question_marks: bool,
+    wrong_flags: WrongFlags,
+    chord_style: ChordStyle,
+    solver: Solver,
+    fog: Fog,
+}
+
+impl Replay {
+    /// The game on `board` so far, played by `settings`.
+    pub fn new(board: &Board, settings: &Settings) -> Self {
+        Self {
+            width: board.width(),
+            height: board.height(),
+            mines: board.mines,
+            variant: board.variant(),
+            seed: board.seed,
+            layout: board.fixed_layout.then(|| board.mine_layout()),
+            given: board.given.clone(),
+            rules: Rules {
+                question_marks: settings.question_marks,
+                wrong_flags: settings.wrong_flags,
+                chord_style: settings.chord_style,
+                solver: settings.solver,
+                fog: settings.fog,
+            },
+            actions: board.history.clone(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        fs::read_to_string(path)
+            .wrap_err_with(|| format!("reading {}", path.display()))?
+            .parse()
+            .wrap_err_with(|| format!("parsing {}", path.display()))
+    }
+
+    /// Saves the replay into the replays directory in the user's data directory, returning where
+    /// it was written.
+    pub fn save(&self) -> Result<Option<PathBuf>> {
+        let Some(dir) = dirs::data_dir().map(|dir| dir.join("minesweeper").join(REPLAYS_DIR))
+        else {
+            return Ok(None);
+        };
+
+        fs::create_dir_all(&dir).wrap_err_with(|| format!("creating {}", dir.display()))?;
+
+        let path = dir.join(format!("{}.{REPLAY_EXTENSION}", unix_now()));
+        fs::write(&path, self.to_string())
+            .wrap_err_with(|| format!("writing {}", path.display()))?;
+
+        Ok(Some(path))
+    }
+
+    /// A fresh board to play the replay back on.
+    pub fn board(&self) -> Board {
+        match &self.layout {
+            Some(layout) => layout_board(layout, self.given.as_ref(), self.variant),
+            None => Board::new(self.width, self.height, self.mines, self.seed, self.variant),
+        }
+    }
+
+    /// `settings` with the rules the game was played by, to play it back with.
+    pub fn settings(&self, settings: &Settings) -> Settings {
+        Settings {
+            question_marks: self.rules.question_marks,
+            wrong_flags: self.rules.wrong_flags,
+            chord_style: self.rules.chord_style,
+            solver: self.rules.solver,
+            fog: self.rules.fog,
+            ..settings.clone()
+        }
+    }
+
+    /// The board with every move in the replay already made on it.
+    pub fn replayed(&self, settings: &Settings) -> Board {
+        let settings = self.settings(settings);
+        let mut board = self.board();
+
+        for &(_, action) in &self.actions {
+            board.apply(action, &settings);
+        }
+
+        board.settle();
+        board
+    }
+
+    /// Seconds from the first click to the last move.
+    pub fn seconds(&self) -> f32 {
+        self.actions.last().map_or(0.0, |&(seconds, _)| seconds)
+    }
+
+    pub fn playback(self) -> Playback {
+        Playback {
+            actions: self.actions.into(),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "size {} {} {}", self.width, self.height, self.mines)?;
+
+        if self.variant.topology != Topology::Flat {
+            writeln!(f, "{}", self.variant.topology.name().to_lowercase())?;
+        }
+
+        if self.variant.mines_per_tile > 1 {
+            writeln!(f, "mines-per-tile {}", self.variant.mines_per_tile)?;
+        }
+
+        if self.variant.clues != Clues::Classic {
+            writeln!(f, "clues {}", self.variant.clues.name().to_lowercase())?;
+        }
+
+        match &self.layout {
+            Some(layout) => {
+                for row in layout_rows(layout, self.given.as_ref()) {
+                    writeln!(f, "row {row}")?;
+                }
+            }
+            None => writeln!(f, "seed {}", self.seed)?,
+        }
+
+        let rules = &self.rules;
+        let default = Rules::default();
+
+        if rules.question_marks != default.question_marks {
+            let on = if rules.question_marks { "on" } else { "off" };
+            writeln!(f, "question-marks {on}")?;
+        }
+
+        if rules.wrong_flags != default.wrong_flags {
+            writeln!(f, "wrong-flags {}", setting_name(rules.wrong_flags))?;
+        }
+
+        if rules.chord_style != default.chord_style {
+            writeln!(f, "chord-style {}", setting_name(rules.chord_style))?;
+        }
+
+        if rules.solver != default.solver {
+            writeln!(f, "solver {}", setting_name(rules.solver))?;
+        }
+
+        if rules.fog != default.fog {
+            writeln!(f, "fog {}", setting_name(rules.fog))?;
+        }
+
+        for (seconds, action) in &self.actions {
+            writeln!(f, "{seconds:.3} {action}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Replay {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut size = None;
+        let mut seed = 0;
+        let mut variant = Variant::default();
+        let mut rules = Rules::default();
+        let mut rows = vec![];
+        let mut actions = vec![];
+
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            let context = || format!("line {}", number + 1);
+
+            let Some((keyword, rest)) = line.split_once(' ') else {
+                if let Some(named) = Topology::ALL
+                    .into_iter()
+                    .find(|topology| topology.name().eq_ignore_ascii_case(line))
+                {
+                    variant.topology = named;
+                } else if !line.is_empty() && !line.starts_with('#') {
+                    bail!("{}: unexpected {line:?}", context());
+                }
+                continue;
+            };
+
+            match keyword {
+                "size" => {
+                    let numbers = rest
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<Vec<usize>, _>>()
+                        .wrap_err_with(context)?;
+
+                    let [width, height, mines] = numbers[..] else {
+                        bail!("{}: expected a width, height and mine count", context());
+                    };
+                    size = Some((width, height, mines));
+                }
+                "seed" => seed = rest.trim().parse().wrap_err_with(context)?,
+                "mines-per-tile" => {
+                    variant.mines_per_tile = rest.trim().parse().wrap_err_with(context)?;
+                    ensure!(
+                        (1..=MAX_MINES_PER_TILE).contains(&variant.mines_per_tile),
+                        "{}: a tile holds between 1 and {MAX_MINES_PER_TILE} mines",
+                        context()
+                    );
+                }
+                "clues" => {
+                    variant.clues = Clues::ALL
+                        .into_iter()
+                        .find(|clues| clues.name().eq_ignore_ascii_case(rest.trim()))
+                        .ok_or_else(|| eyre!("{}: unknown clues {rest:?}", context()))?;
+                }
+                "question-marks" => {
+                    rules.question_marks = match rest.trim() {
+                        "on" => true,
+                        "off" => false,
+                        _ => bail!("{}: expected on or off", context()),
+                    };
+                }
+                "wrong-flags" => rules.wrong_flags = parse_setting(rest).wrap_err_with(context)?,
+                "chord-style" => rules.chord_style = parse_setting(rest).wrap_err_with(context)?,
+                "solver" => rules.solver = parse_setting(rest).wrap_err_with(context)?,
+                "fog" => rules.fog = parse_setting(rest).wrap_err_with(context)?,
+                "row" => rows.push(rest.trim()),
+                _ if keyword.starts_with('#') => {}
+                _ => {
+                    let seconds = keyword.parse().wrap_err_with(context)?;
+                    let action = rest.parse().wrap_err_with(context)?;
+                    actions.push((seconds, action));
+                }
+            }
+        }
+
+        // replays come from other players over the network too, so nothing is taken on trust
+        let (width, height, mines) = size.ok_or_else(|| eyre!("missing size line"))?;
+        ensure!(width > 0 && height > 0, "the board has no tiles");
+        variant.check_size(width, height)?;
+        ensure!(
+            !rows.is_empty() || mines <= variant.max_mines(width, height),
+            "a {width}x{height} board fits at most {} mines",
+            variant.max_mines(width, height)
+        );
+
+        let (layout, given) = if rows.is_empty() {
+            (None, None)
+        } else {
+            let (layout, open) = parse_rows(&rows)?;
+            ensure!(
+                (layout.width(), layout.height()) == (width, height),
+                "the rows do not match the {width}x{height} size"
+            );
+            let given = open.iter().any(|&open| open).then_some(open);
+            (Some(layout), given)
+        };
+
+        for (_, action) in &actions {
+            if let Action::Reveal(col, row) | Action::Flag(col, row) | Action::Chord(col, row) =
+                *action
+            {
+                ensure!(col < width && row < height, "{action} is off the board");
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            mines,
+            variant,
+            seed,
+            layout,
+            given,
+            rules,
+            actions,
+        })
+    }
+}
+
+/// A setting's name as the config file has it, such as `left-click`.
+fn setting_name(setting: impl Serialize) -> String {
+    match toml::Value::try_from(setting) {
+        Ok(toml::Value::String(name)) => name,
+        _ => unreachable!("settings are named by their variants"),
+    }
+}
+
+/// Reads a setting named as the config file has it.
+fn parse_setting<T: DeserializeOwned>(name: &str) -> Result<T> {
+    T::deserialize(toml::Value::String(name.trim().to_owned()))
+        .map_err(|_| eyre!("unknown setting {:?}", name.trim()))
+}
+
+/// Moves from a replay, handed out as the time they were made at comes round again.
+pub struct Playback {
+    actions: VecDeque<(f32, Action)>,
+    start: Instant,
+}
+
+impl Playback {
+    pub fn due(&mut self) -> Vec<Action> {
+        let now = self.start.elapsed().as_secs_f32();
+        let mut due = vec![];
+
+        while let Some(&(seconds, action)) = self.actions.front() {
+            if seconds > now {
+                break;
+            }
+
+            due.push(action);
+            self.actions.pop_front();
+        }
+
+        due
+    }
+}
+
+/// Reads a board file: one line per row of the board, with `*` for a mine, `.` for a safe tile,
+/// `-` for a safe tile that starts open and a digit for a tile holding that many mines. Blank
+/// lines and lines starting with `#` are skipped.
+pub fn load_board(path: &Path, variant: Variant) -> Result<Board> {
+    let text = fs::read_to_string(path).wrap_err_with(|| format!("reading {}", path.display()))?;
+    let rows = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect::<Vec<_>>();
+
+    let (layout, open) =
+        parse_rows(&rows).wrap_err_with(|| format!("parsing {}", path.display()))?;
+    variant
+        .check_size(layout.width(), layout.height())
+        .wrap_err_with(|| format!("loading {}", path.display()))?;
+    let given = open.iter().any(|&open| open).then_some(open);
+
+    Ok(layout_board(&layout, given.as_ref(), variant))
+}
+
+/// A board with the mines already placed, and for a puzzle the tiles it starts with open.
+pub fn layout_board(layout: &Grid<u8>, given: Option<&Grid<bool>>, variant: Variant) -> Board {
+    let mut board = Board::with_layout(layout, variant);
+
+    if let Some(given) = given {
+        board.give(given.clone());
+    }
+
+    board
+}
+
+/// Turns rows of `*`, `.`, `-` and digits into how many mines each tile holds and which tiles
+/// start open.
+pub fn parse_rows(rows: &[&str]) -> Result<(Grid<u8>, Grid<bool>)> {
+    ensure!(!rows.is_empty(), "the board has no rows");
+
+    let width = rows[0].chars().count();
+    ensure!(width > 0, "the board has no columns");
+
+    let mut layout = Grid::new(width, rows.len(), 0);
+    let mut open = Grid::new(width, rows.len(), false);
+
+    for (row, line) in rows.iter().enumerate() {
+        ensure!(
+            line.chars().count() == width,
+            "row {} is not {width} tiles wide",
+            row + 1
+        );
+
+        for (col, tile) in line.chars().enumerate() {
+            layout[(col, row)] = match tile {
+                MINE => 1,
+                SAFE => 0,
+                OPEN => {
+                    open[(col, row)] = true;
+                    0
+                }
+                _ => match tile.to_digit(10) {
+                    Some(mines) if (1..=MAX_MINES_PER_TILE as u32).contains(&mines) => mines as u8,
+                    _ => bail!("unexpected {tile:?} in row {}", row + 1),
+                },
+            };
+        }
+    }
+
+    Ok((layout, open))
+}
+
+fn layout_rows(layout: &Grid<u8>, given: Option<&Grid<bool>>) -> Vec<String> {
+    (0..layout.height())
+        .map(|row| {
+            (0..layout.width())
+                .map(|col| match layout[(col, row)] {
+                    0 if given.is_some_and(|given| given[(col, row)]) => OPEN,
+                    0 => SAFE,
+                    1 => MINE,
+                    mines => (b'0' + mines) as char,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TileState;
+
+    #[test]
+    fn boards_too_big_to_play_are_refused() {
+        for replay in [
+            "size 1000000 1000000 10\nseed 1",
+            "size 61 16 10\nseed 1",
+            "size 30 41 10\nseed 1",
+            "size 9 9 73\nseed 1",
+            "size 9 9 18446744073709551615\nseed 1",
+        ] {
+            assert!(replay.parse::<Replay>().is_err(), "{replay:?}");
+        }
+
+        assert!("size 9 9 72\nseed 1".parse::<Replay>().is_ok());
+        assert!("size 60 40 10\nseed 1".parse::<Replay>().is_ok());
+    }
+
+    #[test]
+    fn replays_read_back_as_they_were_written() {
+        for text in [
+            "size 9 9 10\nseed 42\n0.000 reveal 4 4\n1.250 flag 0 0\n2.500 chord 4 4\n",
+            "size 9 9 10\ntorus\nmines-per-tile 2\nclues knight\nseed 7\n0.000 reveal 1 2\n",
+            "size 4 3 4\nhex\nrow *2.-\nrow -...\nrow ...*\n0.000 reveal 2 0\n",
+            "size 3 3 1\nrow *..\nrow ...\nrow ...\n",
+        ] {
+            let replay = text.parse::<Replay>().unwrap();
+            assert_eq!(replay.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn a_saved_game_replays_by_its_own_rules() {
+        let settings = Settings {
+            question_marks: true,
+            wrong_flags: WrongFlags::Clear,
+            chord_style: ChordStyle::LeftClick,
+            solver: Solver::Trivial,
+            fog: Fog::Recent,
+            ..Settings::default()
+        };
+        let mut board = Board::new(9, 9, 10, 42, Variant::default());
+        board.apply(Action::Reveal(4, 4), &settings);
+        let (col, row) = board
+            .tiles
+            .positions()
+            .find(|&(col, row)| !board.revealed(col, row))
+            .unwrap();
+        // flagging twice leaves a question mark only with them on
+        board.apply(Action::Flag(col, row), &settings);
+        board.apply(Action::Flag(col, row), &settings);
+        board.settle();
+
+        let text = Replay::new(&board, &settings).to_string();
+        for line in [
+            "question-marks on",
+            "wrong-flags clear",
+            "chord-style left-click",
+            "solver trivial",
+            "fog recent",
+        ] {
+            assert!(text.lines().any(|written| written == line), "{line:?}");
+        }
+
+        let replay = text.parse::<Replay>().unwrap();
+        assert_eq!(replay.to_string(), text);
+
+        let replayed = replay.replayed(&Settings::default());
+        assert_eq!(replayed.tiles[(col, row)].state, TileState::Question);
+        for (col, row) in board.tiles.positions() {
+            assert_eq!(
+                replayed.tiles[(col, row)].state,
+                board.tiles[(col, row)].state
+            );
+            assert_eq!(replayed.mine(col, row), board.mine(col, row));
+        }
+    }
+
+    #[test]
+    fn unknown_rules_are_refused() {
+        for line in [
+            "question-marks maybe",
+            "wrong-flags burn",
+            "fog thick",
+            "solver",
+        ] {
+            let replay = format!("size 9 9 10\nseed 1\n{line}");
+            assert!(replay.parse::<Replay>().is_err(), "{line:?}");
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, ErrorKind},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use eyre::{Result, WrapErr};
+use serde::Serialize;
+
+use crate::{
+    protocol::{self, Engine, GameState},
+    settings::Settings,
+    Board,
+};
+
+/// What the server sends besides the game itself.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Notice {
+    /// Sent when asked with `scores`, and when the connection has played all its games.
+    Scores { scores: Vec<Score> },
+    /// Sent before closing a connection that took too long over a move.
+    Timeout { seconds: f32 },
+}
+
+#[derive(Serialize, Clone, Default)]
+struct Score {
+    name: String,
+    played: u32,
+    won: u32,
+    /// Games lost by running out of time or disconnecting mid game, which also count as lost.
+    forfeited: u32,
+    /// Fastest win in seconds.
+    best: Option<f32>,
+    /// Seconds spent on all the wins together, for the average.
+    winning_seconds: f32,
+}
+
+impl Score {
+    fn record(&mut self, won: bool, seconds: f32) {
+        self.played += 1;
+
+        if won {
+            self.won += 1;
+            self.winning_seconds += seconds;
+            self.best = Some(self.best.map_or(seconds, |best| best.min(seconds)));
+        }
+    }
+}
+
+/// Results of every bot that has connected, by name.
+#[derive(Default)]
+struct Scoreboard {
+    scores: HashMap<String, Score>,
+}
+
+impl Scoreboard {
+    fn score(&mut self, name: &str) -> &mut Score {
+        self.scores.entry(name.to_owned()).or_insert_with(|| Score {
+            name: name.to_owned(),
+            ..Score::default()
+        })
+    }
+
+    /// Most wins first, then the quickest on average.
+    fn ranked(&self) -> Vec<Score> {
+        let average = |score: &Score| score.winning_seconds / score.won.max(1) as f32;
+        let mut scores = self.scores.values().cloned().collect::<Vec<_>>();
+        scores.sort_by(|a, b| b.won.cmp(&a.won).then(average(a).total_cmp(&average(b))));
+        scores
+    }
+
+    fn print(&self) {
+        println!(
+            "{:<20} {:>6} {:>6} {:>9} {:>8} {:>8}",
+            "bot", "played", "won", "forfeited", "best", "average"
+        );
+
+        for score in self.ranked() {
+            let best = score
+                .best
+                .map_or("-".to_owned(), |best| format!("{best:.3}"));
+            let average = if score.won > 0 {
+                format!("{:.3}", score.winning_seconds / score.won as f32)
+            } else {
+                "-".to_owned()
+            };
+
+            println!(
+                "{:<20} {:>6} {:>6} {:>9} {:>8} {:>8}",
+                score.name, score.played, score.won, score.forfeited, best, average
+            );
+        }
+    }
+}
+
+/// Hosts games for bots connecting to `port` on this machine only, each on its own thread and
+/// speaking the same protocol as `--protocol`. Every connection starts on its own seed, drawn
+/// in turn from the server's seed, and counts up from there. The seed is in the board message
+/// each game starts with, so any game can be played again.
+///
+/// Besides moves, a bot can send `name <name>` to be scored under a name rather than its
+/// connection number, and `scores` for the scoreboard. A bot that takes longer than
+/// `move_limit` to answer is disconnected, forfeiting the game it is on if it has made a move
+/// in it, the same as a bot that leaves.
+pub fn run(
+    port: u16,
+    board: Board,
+    settings: Settings,
+    move_limit: Duration,
+    games: Option<u32>,
+) -> Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .wrap_err_with(|| format!("listening on port {port}"))?;
+    let scoreboard = Arc::new(Mutex::new(Scoreboard::default()));
+    let mut seeds = fastrand::Rng::with_seed(board.seed);
+
+    println!(
+        "waiting for bots on {}, seed {}",
+        listener.local_addr()?,
+        board.seed
+    );
+
+    for (number, stream) in listener.incoming().enumerate() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                eprintln!("failed to accept a bot: {error}");
+                continue;
+            }
+        };
+
+        let name = format!("bot-{}", number + 1);
+        let seed = seeds.u64(..);
+        println!("{name} connected, seed {seed}");
+
+        let connection = Connection {
+            name,
+            engine: Engine::new(board.reseeded(seed), settings.clone()),
+            scoreboard: Arc::clone(&scoreboard),
+            move_limit,
+            games,
+        };
+
+        let scoreboard = Arc::clone(&scoreboard);
+        thread::spawn(move || {
+            let name = connection.name.clone();
+
+            if let Err(error) = connection.serve(stream) {
+                eprintln!("{name}: {error:?}");
+            }
+
+            scoreboard.lock().unwrap().print();
+        });
+    }
+
+    Ok(())
+}
+
+struct Connection {
+    name: String,
+    engine: Engine,
+    scoreboard: Arc<Mutex<Scoreboard>>,
+    move_limit: Duration,
+    games: Option<u32>,
+}
+
+impl Connection {
+    fn serve(mut self, stream: TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(self.move_limit))?;
+        let mut messages = stream.try_clone()?;
+        let mut moves = BufReader::new(stream);
+        let mut finished = 0;
+
+        protocol::send(&mut messages, &self.engine.board_message())?;
+
+        loop {
+            let asked = Instant::now();
+            let mut line = String::new();
+
+            let read = moves.read_line(&mut line);
+            let late = asked.elapsed() > self.move_limit;
+
+            match read {
+                Ok(0) => break,
+                Ok(_) if !late => {}
+                Err(error) if !late && error.kind() == ErrorKind::Interrupted => continue,
+                Err(error)
+                    if !matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    return Err(error).wrap_err("reading a move")
+                }
+                _ => {
+                    let seconds = self.move_limit.as_secs_f32();
+                    println!("{} ran out of time", self.name);
+                    self.forfeit();
+
+                    return protocol::send(&mut messages, &Notice::Timeout { seconds });
+                }
+            }
+
+            let line = line.trim();
+            if let Some(name) = line.strip_prefix("name ") {
+                self.name = name.trim().to_owned();
+                continue;
+            }
+
+            match line {
+                "" => continue,
+                "scores" => {
+                    let scores = self.scoreboard.lock().unwrap().ranked();
+                    protocol::send(&mut messages, &Notice::Scores { scores })?;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let was_finished = self.engine.finished();
+            let message = self.engine.play(line);
+            protocol::send(&mut messages, &message)?;
+
+            if was_finished || !self.engine.finished() {
+                continue;
+            }
+
+            let won = self.engine.state() == GameState::Won;
+            let seconds = self.engine.seconds();
+            self.scoreboard
+                .lock()
+                .unwrap()
+                .score(&self.name)
+                .record(won, seconds);
+            println!(
+                "{} {} in {seconds:.3}s",
+                self.name,
+                if won { "won" } else { "lost" }
+            );
+
+            finished += 1;
+            if self.games.is_some_and(|games| finished >= games) {
+                let scores = self.scoreboard.lock().unwrap().ranked();
+                return protocol::send(&mut messages, &Notice::Scores { scores });
+            }
+        }
+
+        self.forfeit();
+        Ok(())
+    }
+
+    /// Counts the game as lost for a bot that leaves or runs out of time in the middle of it,
+    /// so bots can't walk away from boards they don't like. Going before the first move or
+    /// between games costs nothing.
+    fn forfeit(&mut self) {
+        if self.engine.state() != GameState::Playing {
+            return;
+        }
+
+        let seconds = self.engine.seconds();
+        let mut scoreboard = self.scoreboard.lock().unwrap();
+        let score = scoreboard.score(&self.name);
+
+        score.record(false, seconds);
+        score.forfeited += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{Difficulty, Variant};
+
+    #[test]
+    fn only_games_under_way_are_forfeited() {
+        let board = crate::new_board(Difficulty::Beginner, Variant::default(), 1);
+        let mut connection = Connection {
+            name: "bot".to_owned(),
+            engine: Engine::new(board, Settings::default()),
+            scoreboard: Arc::default(),
+            move_limit: Duration::from_secs(1),
+            games: None,
+        };
+
+        connection.forfeit();
+        assert_eq!(connection.scoreboard.lock().unwrap().scores.len(), 0);
+
+        connection.engine.play("reveal 4 4");
+        assert!(connection.engine.state() == GameState::Playing);
+        connection.forfeit();
+
+        let scores = connection.scoreboard.lock().unwrap().ranked();
+        assert_eq!((scores[0].played, scores[0].forfeited), (1, 1));
+    }
+}
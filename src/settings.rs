@@ -0,0 +1,503 @@
+use eyre::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    grid::{Clues, Topology},
+    palette::{NumberStyle, Palette},
+};
+
+pub const MIN_CUSTOM_SIZE: usize = 8;
+pub const MAX_CUSTOM_WIDTH: usize = 60;
+pub const MAX_CUSTOM_HEIGHT: usize = 40;
+pub const MAX_MINES_PER_TILE: u8 = 3;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Difficulty {
+    Beginner,
+    Intermediate,
+    Expert,
+    Custom {
+        width: usize,
+        height: usize,
+        mines: usize,
+    },
+}
+
+impl Difficulty {
+    pub const STANDARD: [Difficulty; 3] = [
+        Difficulty::Beginner,
+        Difficulty::Intermediate,
+        Difficulty::Expert,
+    ];
+
+    /// A custom board brought within the size limits, leaving room for the empty opening
+    /// around the first click.
+    pub fn custom(width: usize, height: usize, mines: usize, variant: Variant) -> Self {
+        let width = width.clamp(MIN_CUSTOM_SIZE, MAX_CUSTOM_WIDTH);
+        let height = height.clamp(MIN_CUSTOM_SIZE, MAX_CUSTOM_HEIGHT);
+
+        Difficulty::Custom {
+            width,
+            height,
+            mines: mines.clamp(1, variant.max_mines(width, height)),
+        }
+    }
+
+    pub fn width(self) -> usize {
+        match self {
+            Difficulty::Beginner => 9,
+            Difficulty::Intermediate => 16,
+            Difficulty::Expert => 30,
+            Difficulty::Custom { width, .. } => width,
+        }
+    }
+
+    pub fn height(self) -> usize {
+        match self {
+            Difficulty::Beginner => 9,
+            Difficulty::Intermediate => 16,
+            Difficulty::Expert => 16,
+            Difficulty::Custom { height, .. } => height,
+        }
+    }
+
+    pub fn mines(self) -> usize {
+        match self {
+            Difficulty::Beginner => 10,
+            Difficulty::Intermediate => 40,
+            Difficulty::Expert => 99,
+            Difficulty::Custom { mines, .. } => mines,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "Beginner",
+            Difficulty::Intermediate => "Intermediate",
+            Difficulty::Expert => "Expert",
+            Difficulty::Custom { .. } => "Custom",
+        }
+    }
+}
+
+/// The rules of the board, beyond its size and how many mines it has.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Variant {
+    pub topology: Topology,
+    /// Most mines one tile can hold, and so how many flags it takes. 1 is the classic game.
+    pub mines_per_tile: u8,
+    pub clues: Clues,
+}
+
+impl Default for Variant {
+    fn default() -> Self {
+        Self {
+            topology: Topology::default(),
+            mines_per_tile: 1,
+            clues: Clues::default(),
+        }
+    }
+}
+
+impl Variant {
+    pub fn next_mines_per_tile(&mut self) {
+        self.mines_per_tile = self.mines_per_tile % MAX_MINES_PER_TILE + 1;
+    }
+
+    /// Checks that a `width` by `height` board can be played with these rules. Boards are no
+    /// bigger than custom games can be, and a torus has to be big enough that no clue wraps
+    /// all the way round onto a tile it already counts.
+    pub fn check_size(self, width: usize, height: usize) -> Result<()> {
+        ensure!(
+            width <= MAX_CUSTOM_WIDTH && height <= MAX_CUSTOM_HEIGHT,
+            "a {width}x{height} board is bigger than {MAX_CUSTOM_WIDTH}x{MAX_CUSTOM_HEIGHT}"
+        );
+
+        let wraps = self.topology == Topology::Torus && width >= 3 && height >= 3;
+        let across = 2 * self.clues.reach(self.topology) + 1;
+
+        ensure!(
+            !wraps || (width >= across && height >= across),
+            "a torus with {} clues has to be at least {across} tiles across and down",
+            self.clues.name().to_lowercase()
+        );
+        Ok(())
+    }
+
+    /// Most mines a `width` by `height` board can hold while keeping the first click and the
+    /// tiles its clue covers free of mines.
+    pub fn max_mines(self, width: usize, height: usize) -> usize {
+        let opening = self.clues.offsets(self.topology, 0, 0).len() + 1;
+        (width * height).saturating_sub(opening) * self.mines_per_tile as usize
+    }
+}
+
+/// Who makes the moves on the board.
+#[derive(PartialEq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    #[default]
+    Human,
+    Bot,
+    /// The player makes the moves, and can ask the bot to make a pass over the board with space.
+    Assisted,
+    /// The player races the bot on a second board with the same mines.
+    RaceBot,
+    /// The player on the mouse races a second player on the keyboard, each on their own board
+    /// with the same mines.
+    RaceKeyboard,
+}
+
+impl Mode {
+    pub fn next(self) -> Self {
+        match self {
+            Mode::Human => Mode::Bot,
+            Mode::Bot => Mode::Assisted,
+            Mode::Assisted => Mode::RaceBot,
+            Mode::RaceBot => Mode::RaceKeyboard,
+            Mode::RaceKeyboard => Mode::Human,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Human => "Human",
+            Mode::Bot => "Bot",
+            Mode::Assisted => "Assisted",
+            Mode::RaceBot => "Race bot",
+            Mode::RaceKeyboard => "Race keyboard",
+        }
+    }
+
+    pub fn racing(self) -> bool {
+        matches!(self, Mode::RaceBot | Mode::RaceKeyboard)
+    }
+}
+
+/// How much the bot knows when it plays.
+#[derive(PartialEq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Solver {
+    /// Only flags and clears around single numbers.
+    Trivial,
+    /// Also recognises 1-2-1 patterns along an edge of revealed tiles.
+    #[default]
+    Patterns,
+}
+
+impl Solver {
+    pub fn next(self) -> Self {
+        match self {
+            Solver::Trivial => Solver::Patterns,
+            Solver::Patterns => Solver::Trivial,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Solver::Trivial => "Trivial",
+            Solver::Patterns => "Patterns",
+        }
+    }
+}
+
+/// Which clicks on a satisfied number reveal the tiles around it.
+#[derive(PartialEq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChordStyle {
+    /// Releasing left and right together, or the middle button.
+    #[default]
+    Classic,
+    /// A plain left click on a revealed number, as well as the classic clicks.
+    LeftClick,
+    Disabled,
+}
+
+impl ChordStyle {
+    pub fn next(self) -> Self {
+        match self {
+            ChordStyle::Classic => ChordStyle::LeftClick,
+            ChordStyle::LeftClick => ChordStyle::Disabled,
+            ChordStyle::Disabled => ChordStyle::Classic,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChordStyle::Classic => "Classic",
+            ChordStyle::LeftClick => "Left click",
+            ChordStyle::Disabled => "Off",
+        }
+    }
+}
+
+/// What opening up empty space does with flags in the way. Every tile it reaches is safe, so
+/// those flags are always wrong.
+#[derive(PartialEq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WrongFlags {
+    /// Leave them, and stop opening up at them.
+    #[default]
+    Keep,
+    /// Remove them and open the tiles underneath, counting each one in the stats.
+    Clear,
+}
+
+impl WrongFlags {
+    pub fn next(self) -> Self {
+        match self {
+            WrongFlags::Keep => WrongFlags::Clear,
+            WrongFlags::Clear => WrongFlags::Keep,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WrongFlags::Keep => "Keep",
+            WrongFlags::Clear => "Clear",
+        }
+    }
+}
+
+/// Which revealed numbers can be read. The rest fade to a blank the player has to remember,
+/// and the bot only works from the numbers in sight.
+#[derive(PartialEq, Debug, Default, Clone, Copy, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fog {
+    #[default]
+    Off,
+    /// Numbers on and next to the tiles opened by the last few reveals.
+    Recent,
+    /// Numbers within a couple of tiles of the mouse.
+    Cursor,
+}
+
+impl Fog {
+    pub fn next(self) -> Self {
+        match self {
+            Fog::Off => Fog::Recent,
+            Fog::Recent => Fog::Cursor,
+            Fog::Cursor => Fog::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Fog::Off => "Off",
+            Fog::Recent => "Recent",
+            Fog::Cursor => "Cursor",
+        }
+    }
+}
+
+/// How many decimal places finishing times are shown with. The counter on the board only has
+/// room for whole seconds.
+#[derive(PartialEq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimerPrecision {
+    #[default]
+    Seconds,
+    Tenths,
+    Hundredths,
+}
+
+impl TimerPrecision {
+    pub fn next(self) -> Self {
+        match self {
+            TimerPrecision::Seconds => TimerPrecision::Tenths,
+            TimerPrecision::Tenths => TimerPrecision::Hundredths,
+            TimerPrecision::Hundredths => TimerPrecision::Seconds,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TimerPrecision::Seconds => "1s",
+            TimerPrecision::Tenths => "0.1s",
+            TimerPrecision::Hundredths => "0.01s",
+        }
+    }
+
+    pub fn format(self, seconds: f32) -> String {
+        match self {
+            TimerPrecision::Seconds => format!("{}s", seconds as u32),
+            TimerPrecision::Tenths => format!("{:.1}s", (seconds * 10.0).floor() / 10.0),
+            TimerPrecision::Hundredths => format!("{:.2}s", (seconds * 100.0).floor() / 100.0),
+        }
+    }
+}
+
+pub const SCALES: [f32; 4] = [1.0, 1.5, 2.0, 2.5];
+
+#[derive(Clone)]
+pub struct Settings {
+    pub difficulty: Difficulty,
+    pub custom: Difficulty,
+    pub mode: Mode,
+    pub variant: Variant,
+    pub fog: Fog,
+    pub solver: Solver,
+    pub scale: f32,
+    /// Whether wrapping boards show the opposite edges past their own.
+    pub ghost_edges: bool,
+    pub palette: Palette,
+    pub number_style: NumberStyle,
+    pub question_marks: bool,
+    pub chord_style: ChordStyle,
+    pub wrong_flags: WrongFlags,
+    pub timer_precision: TimerPrecision,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            difficulty: Difficulty::Expert,
+            custom: Difficulty::Custom {
+                width: 30,
+                height: 16,
+                mines: 99,
+            },
+            mode: Mode::default(),
+            variant: Variant::default(),
+            fog: Fog::default(),
+            solver: Solver::default(),
+            scale: 1.5,
+            ghost_edges: true,
+            palette: Palette::default(),
+            number_style: NumberStyle::default(),
+            question_marks: false,
+            chord_style: ChordStyle::default(),
+            wrong_flags: WrongFlags::default(),
+            timer_precision: TimerPrecision::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn next_scale(&mut self) {
+        let index = SCALES.iter().position(|&s| s == self.scale).unwrap_or(0);
+        self.scale = SCALES[(index + 1) % SCALES.len()];
+    }
+
+    /// Takes on whatever changed from `before` to `after` and leaves the rest alone, so that
+    /// choices made in the menus can be kept apart from the flags given for one run.
+    pub fn keep_changes(&mut self, before: &Settings, after: &Settings) {
+        fn keep<T: PartialEq + Copy>(kept: &mut T, before: T, after: T) {
+            if before != after {
+                *kept = after;
+            }
+        }
+
+        keep(&mut self.difficulty, before.difficulty, after.difficulty);
+        keep(&mut self.custom, before.custom, after.custom);
+        keep(&mut self.mode, before.mode, after.mode);
+        keep(
+            &mut self.variant.topology,
+            before.variant.topology,
+            after.variant.topology,
+        );
+        keep(
+            &mut self.variant.mines_per_tile,
+            before.variant.mines_per_tile,
+            after.variant.mines_per_tile,
+        );
+        keep(
+            &mut self.variant.clues,
+            before.variant.clues,
+            after.variant.clues,
+        );
+        keep(&mut self.fog, before.fog, after.fog);
+        keep(&mut self.solver, before.solver, after.solver);
+        keep(&mut self.scale, before.scale, after.scale);
+        keep(&mut self.ghost_edges, before.ghost_edges, after.ghost_edges);
+        keep(&mut self.palette, before.palette, after.palette);
+        keep(
+            &mut self.number_style,
+            before.number_style,
+            after.number_style,
+        );
+        keep(
+            &mut self.question_marks,
+            before.question_marks,
+            after.question_marks,
+        );
+        keep(&mut self.chord_style, before.chord_style, after.chord_style);
+        keep(&mut self.wrong_flags, before.wrong_flags, after.wrong_flags);
+        keep(
+            &mut self.timer_precision,
+            before.timer_precision,
+            after.timer_precision,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_fair_game;
+
+    #[test]
+    fn max_mines_fills_the_board_around_the_opening() {
+        for topology in Topology::ALL {
+            for clues in Clues::ALL {
+                for mines_per_tile in 1..=MAX_MINES_PER_TILE {
+                    let variant = Variant {
+                        topology,
+                        mines_per_tile,
+                        clues,
+                    };
+                    let (width, height) = (MIN_CUSTOM_SIZE, MIN_CUSTOM_SIZE);
+                    let mines = variant.max_mines(width, height);
+                    let (start_col, start_row) = (width / 2, height / 2);
+
+                    let tiles = generate_fair_game(
+                        start_col,
+                        start_row,
+                        width,
+                        height,
+                        mines,
+                        variant,
+                        &mut fastrand::Rng::with_seed(0),
+                    );
+
+                    let placed = tiles.iter().map(|tile| tile.mines as usize).sum::<usize>();
+                    assert_eq!(placed, mines, "{variant:?}");
+                    assert_eq!(tiles[(start_col, start_row)].neighbour_mines_count, 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn small_tori_only_take_clues_that_fit() {
+        let torus = |clues| Variant {
+            topology: Topology::Torus,
+            clues,
+            ..Variant::default()
+        };
+
+        assert!(torus(Clues::Classic).check_size(3, 3).is_ok());
+        assert!(torus(Clues::Knight).check_size(4, 8).is_err());
+        assert!(torus(Clues::Cross).check_size(8, 3).is_err());
+        assert!(torus(Clues::Knight).check_size(5, 5).is_ok());
+        // too small to wrap at all, so played flat
+        assert!(torus(Clues::Knight).check_size(2, 8).is_ok());
+    }
+
+    #[test]
+    fn custom_boards_keep_within_the_limits() {
+        let variant = Variant {
+            topology: Topology::Triangle,
+            ..Variant::default()
+        };
+
+        assert_eq!(
+            Difficulty::custom(1000, 1, usize::MAX, variant),
+            Difficulty::Custom {
+                width: MAX_CUSTOM_WIDTH,
+                height: MIN_CUSTOM_SIZE,
+                mines: MAX_CUSTOM_WIDTH * MIN_CUSTOM_SIZE - 13,
+            }
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Difficulty;
+
+const STATS_FILE: &str = "stats.toml";
+const LEADERBOARD_SIZE: usize = 10;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct Record {
+    pub seconds: f32,
+    /// Unix time the game was won at.
+    pub date: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct DifficultyStats {
+    pub played: u32,
+    pub won: u32,
+    /// The fastest wins, quickest first.
+    pub leaderboard: Vec<Record>,
+    #[serde(default)]
+    pub tiles_opened: u64,
+    /// Flags removed by opening up empty space, when wrong flags are set to be cleared.
+    #[serde(default)]
+    pub wrong_flags: u64,
+}
+
+impl DifficultyStats {
+    pub fn win_percentage(&self) -> f32 {
+        if self.played == 0 {
+            0.0
+        } else {
+            100.0 * self.won as f32 / self.played as f32
+        }
+    }
+}
+
+/// How the first game on a daily board went.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DailyResult {
+    /// Days since 1970-01-01 of the board.
+    pub day: u64,
+    pub won: bool,
+    pub seconds: f32,
+    /// The fewest clicks the board could have been cleared in.
+    pub bbbv: usize,
+}
+
+/// Answers given in the pattern trainer for one pattern.
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+pub struct PatternStats {
+    pub answered: u32,
+    pub right: u32,
+}
+
+/// Results of every game played by a human, kept in the user's data directory between runs.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Stats {
+    #[serde(default)]
+    beginner: DifficultyStats,
+    #[serde(default)]
+    intermediate: DifficultyStats,
+    #[serde(default)]
+    expert: DifficultyStats,
+    #[serde(default)]
+    custom: DifficultyStats,
+    #[serde(default)]
+    daily: Vec<DailyResult>,
+    /// Pattern trainer answers, by the name of the pattern.
+    #[serde(default)]
+    patterns: BTreeMap<String, PatternStats>,
+}
+
+impl Stats {
+    pub fn load() -> Self {
+        let Some(path) = stats_path() else {
+            return Self::default();
+        };
+
+        fs::read_to_string(&path)
+            .ok()
+            .and_then(|stats| match toml::from_str(&stats) {
+                Ok(stats) => Some(stats),
+                Err(error) => {
+                    eprintln!("ignoring unreadable {}: {error}", path.display());
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = stats_path() else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).wrap_err_with(|| format!("creating {}", dir.display()))?;
+        }
+
+        fs::write(&path, toml::to_string(self)?)
+            .wrap_err_with(|| format!("writing {}", path.display()))
+    }
+
+    pub fn get(&self, difficulty: Difficulty) -> &DifficultyStats {
+        match difficulty {
+            Difficulty::Beginner => &self.beginner,
+            Difficulty::Intermediate => &self.intermediate,
+            Difficulty::Expert => &self.expert,
+            Difficulty::Custom { .. } => &self.custom,
+        }
+    }
+
+    fn get_mut(&mut self, difficulty: Difficulty) -> &mut DifficultyStats {
+        match difficulty {
+            Difficulty::Beginner => &mut self.beginner,
+            Difficulty::Intermediate => &mut self.intermediate,
+            Difficulty::Expert => &mut self.expert,
+            Difficulty::Custom { .. } => &mut self.custom,
+        }
+    }
+
+    /// Records a finished game, returning the leaderboard position of a win that made it on.
+    /// Custom boards vary too much to compare, so they are counted but never ranked.
+    pub fn record(
+        &mut self,
+        difficulty: Difficulty,
+        won: bool,
+        seconds: f32,
+        tiles_opened: usize,
+        wrong_flags: usize,
+    ) -> Option<usize> {
+        let stats = self.get_mut(difficulty);
+        stats.played += 1;
+        stats.tiles_opened += tiles_opened as u64;
+        stats.wrong_flags += wrong_flags as u64;
+
+        if !won {
+            return None;
+        }
+
+        stats.won += 1;
+
+        if let Difficulty::Custom { .. } = difficulty {
+            return None;
+        }
+
+        let position = stats
+            .leaderboard
+            .iter()
+            .position(|record| seconds < record.seconds)
+            .unwrap_or(stats.leaderboard.len());
+
+        if position >= LEADERBOARD_SIZE {
+            return None;
+        }
+
+        stats.leaderboard.insert(
+            position,
+            Record {
+                seconds,
+                date: unix_now(),
+            },
+        );
+        stats.leaderboard.truncate(LEADERBOARD_SIZE);
+
+        Some(position)
+    }
+
+    pub fn pattern(&self, name: &str) -> PatternStats {
+        self.patterns.get(name).copied().unwrap_or_default()
+    }
+
+    pub fn record_pattern(&mut self, name: &str, right: bool) {
+        let stats = self.patterns.entry(name.to_owned()).or_default();
+        stats.answered += 1;
+        stats.right += u32::from(right);
+    }
+
+    pub fn daily(&self) -> &[DailyResult] {
+        &self.daily
+    }
+
+    /// Records the result of a daily board unless the day already has one, returning whether
+    /// it was recorded.
+    pub fn record_daily(&mut self, result: DailyResult) -> bool {
+        if self.daily.iter().any(|daily| daily.day == result.day) {
+            return false;
+        }
+
+        self.daily.push(result);
+        true
+    }
+
+    /// Daily boards won in a row up to `day`, or up to the day before while `day` hasn't been
+    /// played yet.
+    pub fn daily_streak(&self, day: u64) -> usize {
+        let won = |day| self.daily.iter().any(|daily| daily.day == day && daily.won);
+        let last = if self.daily.iter().any(|daily| daily.day == day) {
+            day
+        } else {
+            day.saturating_sub(1)
+        };
+
+        (0..=last).rev().take_while(|&day| won(day)).count()
+    }
+}
+
+fn stats_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("minesweeper").join(STATS_FILE))
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Formats unix time as a `YYYY-MM-DD` date in UTC.
+pub fn format_date(unix: u64) -> String {
+    let (year, month, day) = civil_from_days((unix / 86_400) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Converts days since 1970-01-01 into a proleptic Gregorian (year, month, day), following
+/// Howard Hinnant's `civil_from_days`.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
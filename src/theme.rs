@@ -0,0 +1,303 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, WrapErr};
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+use crate::config::config_dir;
+
+/// Name of the directory, in the working directory, next to the executable or in the user's
+/// config directory, that holds one subdirectory per theme.
+pub const THEMES_DIR: &str = "themes";
+const MANIFEST: &str = "theme.toml";
+/// Name of the built in theme.
+pub const CLASSIC: &str = "Classic";
+
+#[derive(Clone)]
+pub struct Textures {
+    pub tile: Texture2D,
+    pub flag: Texture2D,
+    pub mine: Texture2D,
+    pub cross: Texture2D,
+    pub smiley: Texture2D,
+    pub smiley_open: Texture2D,
+    pub smiley_dead: Texture2D,
+    pub smiley_clicked: Texture2D,
+    pub smiley_glasses: Texture2D,
+    pub neighbour_mines: [Texture2D; 8],
+    pub counter_digits: [Texture2D; 10],
+}
+
+#[macro_export]
+macro_rules! load_texture {
+    ( $path:tt ) => {
+        Texture2D::from_file_with_format(include_bytes!($path), None)
+    };
+}
+
+impl Textures {
+    /// The classic textures compiled into the binary, used by the default theme and for any
+    /// image a theme on disk leaves out.
+    pub fn builtin() -> Self {
+        Self {
+            tile: load_texture!("../assets/tile.png"),
+            flag: load_texture!("../assets/flag.png"),
+            mine: load_texture!("../assets/mine.png"),
+            cross: load_texture!("../assets/cross.png"),
+            smiley: load_texture!("../assets/smiley.png"),
+            smiley_open: load_texture!("../assets/smiley_open.png"),
+            smiley_dead: load_texture!("../assets/smiley_dead.png"),
+            smiley_clicked: load_texture!("../assets/smiley_clicked.png"),
+            smiley_glasses: load_texture!("../assets/smiley_glasses.png"),
+            neighbour_mines: [
+                load_texture!("../assets/1.png"),
+                load_texture!("../assets/2.png"),
+                load_texture!("../assets/3.png"),
+                load_texture!("../assets/4.png"),
+                load_texture!("../assets/5.png"),
+                load_texture!("../assets/6.png"),
+                load_texture!("../assets/7.png"),
+                load_texture!("../assets/8.png"),
+            ],
+            counter_digits: [
+                load_texture!("../assets/0_counter.png"),
+                load_texture!("../assets/1_counter.png"),
+                load_texture!("../assets/2_counter.png"),
+                load_texture!("../assets/3_counter.png"),
+                load_texture!("../assets/4_counter.png"),
+                load_texture!("../assets/5_counter.png"),
+                load_texture!("../assets/6_counter.png"),
+                load_texture!("../assets/7_counter.png"),
+                load_texture!("../assets/8_counter.png"),
+                load_texture!("../assets/9_counter.png"),
+            ],
+        }
+    }
+
+    /// Loads every image in `dir` that uses the same file name as the built in asset it
+    /// replaces, falling back to `fallback` for the rest.
+    fn load(dir: &Path, fallback: &Textures) -> Result<Self> {
+        let neighbour_mines = (0..8)
+            .map(|i| load_or(dir, &format!("{}.png", i + 1), &fallback.neighbour_mines[i]))
+            .collect::<Result<Vec<_>>>()?;
+        let counter_digits = (0..10)
+            .map(|i| {
+                load_or(
+                    dir,
+                    &format!("{i}_counter.png"),
+                    &fallback.counter_digits[i],
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            tile: load_or(dir, "tile.png", &fallback.tile)?,
+            flag: load_or(dir, "flag.png", &fallback.flag)?,
+            mine: load_or(dir, "mine.png", &fallback.mine)?,
+            cross: load_or(dir, "cross.png", &fallback.cross)?,
+            smiley: load_or(dir, "smiley.png", &fallback.smiley)?,
+            smiley_open: load_or(dir, "smiley_open.png", &fallback.smiley_open)?,
+            smiley_dead: load_or(dir, "smiley_dead.png", &fallback.smiley_dead)?,
+            smiley_clicked: load_or(dir, "smiley_clicked.png", &fallback.smiley_clicked)?,
+            smiley_glasses: load_or(dir, "smiley_glasses.png", &fallback.smiley_glasses)?,
+            neighbour_mines: neighbour_mines.try_into().unwrap(),
+            counter_digits: counter_digits.try_into().unwrap(),
+        })
+    }
+}
+
+fn load_or(dir: &Path, name: &str, fallback: &Texture2D) -> Result<Texture2D> {
+    let path = dir.join(name);
+
+    if !path.exists() {
+        return Ok(fallback.clone());
+    }
+
+    let bytes = fs::read(&path).wrap_err_with(|| format!("reading {}", path.display()))?;
+    let image = Image::from_file_with_format(&bytes, None)
+        .wrap_err_with(|| format!("decoding {}", path.display()))?;
+
+    Ok(Texture2D::from_image(&image))
+}
+
+/// Colours of the procedurally drawn frame and menus. Revealed tiles are drawn in the face
+/// colour.
+#[derive(Clone, Copy)]
+pub struct Colours {
+    pub face: Color,
+    pub highlight: Color,
+    pub shadow: Color,
+    pub text: Color,
+}
+
+impl Colours {
+    pub const CLASSIC: Colours = Colours {
+        face: Color::new(192.0 / 255.0, 192.0 / 255.0, 192.0 / 255.0, 1.0),
+        highlight: WHITE,
+        shadow: Color::new(128.0 / 255.0, 128.0 / 255.0, 128.0 / 255.0, 1.0),
+        text: BLACK,
+    };
+}
+
+pub struct Theme {
+    pub name: String,
+    pub author: Option<String>,
+    pub colours: Colours,
+    pub textures: Textures,
+}
+
+/// The `theme.toml` at the root of a theme directory. Anything missing from it is taken from
+/// the classic theme.
+#[derive(Deserialize)]
+struct Manifest {
+    name: String,
+    author: Option<String>,
+    #[serde(default)]
+    colours: ManifestColours,
+}
+
+#[derive(Deserialize, Default)]
+struct ManifestColours {
+    face: Option<[u8; 3]>,
+    highlight: Option<[u8; 3]>,
+    shadow: Option<[u8; 3]>,
+    text: Option<[u8; 3]>,
+}
+
+impl Manifest {
+    fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST);
+        let manifest =
+            fs::read_to_string(&path).wrap_err_with(|| format!("reading {}", path.display()))?;
+
+        toml::from_str(&manifest).wrap_err_with(|| format!("parsing {}", path.display()))
+    }
+}
+
+impl Theme {
+    pub fn classic(textures: Textures) -> Self {
+        Self {
+            name: CLASSIC.to_owned(),
+            author: None,
+            colours: Colours::CLASSIC,
+            textures,
+        }
+    }
+
+    pub fn load(dir: &Path, fallback: &Textures) -> Result<Self> {
+        let manifest = Manifest::load(dir)?;
+
+        let colour = |rgb: Option<[u8; 3]>, fallback: Color| {
+            rgb.map_or(fallback, |[r, g, b]| Color::from_rgba(r, g, b, 255))
+        };
+
+        Ok(Self {
+            name: manifest.name,
+            author: manifest.author,
+            colours: Colours {
+                face: colour(manifest.colours.face, Colours::CLASSIC.face),
+                highlight: colour(manifest.colours.highlight, Colours::CLASSIC.highlight),
+                shadow: colour(manifest.colours.shadow, Colours::CLASSIC.shadow),
+                text: colour(manifest.colours.text, Colours::CLASSIC.text),
+            },
+            textures: Textures::load(dir, fallback)?,
+        })
+    }
+}
+
+/// Every directory containing a theme manifest under the themes directories, sorted by path
+/// so that cycling through them is stable.
+pub fn discover_themes() -> Vec<PathBuf> {
+    let mut roots = vec![PathBuf::from(THEMES_DIR)];
+
+    if let Some(exe_dir) = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        roots.push(exe_dir.join(THEMES_DIR));
+    }
+
+    if let Some(config_dir) = config_dir() {
+        roots.push(config_dir.join(THEMES_DIR));
+    }
+
+    let mut themes = roots
+        .iter()
+        .filter_map(|root| fs::read_dir(root).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.join(MANIFEST).is_file())
+        .filter_map(|path| path.canonicalize().ok())
+        .collect::<Vec<_>>();
+
+    themes.sort();
+    themes.dedup();
+    themes
+}
+
+/// The theme in use, out of the classic theme followed by every theme found on disk.
+pub struct ThemeSelection {
+    builtin: Textures,
+    index: usize,
+    current: Theme,
+}
+
+impl ThemeSelection {
+    pub fn new() -> Self {
+        let builtin = Textures::builtin();
+
+        Self {
+            current: Theme::classic(builtin.clone()),
+            builtin,
+            index: 0,
+        }
+    }
+
+    pub fn current(&self) -> &Theme {
+        &self.current
+    }
+
+    /// Switches to the next theme, rescanning the themes directories so that themes added while
+    /// the game is running show up. A theme that fails to load is skipped back to the classic one.
+    pub fn next(&mut self) {
+        let themes = discover_themes();
+        self.index = (self.index + 1) % (themes.len() + 1);
+        self.load_current(&themes);
+    }
+
+    /// Switches to the theme with the given name, staying on the classic theme if none of the
+    /// themes on disk has it.
+    pub fn select(&mut self, name: &str) {
+        let themes = discover_themes();
+
+        self.index = themes
+            .iter()
+            .position(|dir| Manifest::load(dir).is_ok_and(|manifest| manifest.name == name))
+            .map_or(0, |index| index + 1);
+
+        if self.index == 0 && name != CLASSIC {
+            eprintln!("no theme named {name:?}, using the classic theme");
+        }
+
+        self.load_current(&themes);
+    }
+
+    fn load_current(&mut self, themes: &[PathBuf]) {
+        self.current = if self.index == 0 {
+            Theme::classic(self.builtin.clone())
+        } else {
+            match Theme::load(&themes[self.index - 1], &self.builtin) {
+                Ok(theme) => theme,
+                Err(error) => {
+                    eprintln!("failed to load theme: {error:?}");
+                    self.index = 0;
+                    Theme::classic(self.builtin.clone())
+                }
+            }
+        };
+    }
+}
@@ -0,0 +1,402 @@
+use macroquad::prelude::*;
+
+use crate::{
+    draw_counters, draw_frame, draw_smiley, draw_tiles,
+    grid::Grid,
+    outline_tile_shape,
+    replay::layout_board,
+    settings::{Mode, Settings, Variant},
+    smiley_for,
+    stats::Stats,
+    theme::Theme,
+    versus::draw_status,
+    Action, Board, Layout, State,
+};
+
+// positions are a strip of hidden tiles over a wall of numbers along the bottom edge
+const WIDTH: usize = 10;
+const HEIGHT: usize = 3;
+const WALL: usize = HEIGHT - 1;
+/// Chance in a hundred of each hidden tile holding a mine.
+const MINE_CHANCE: u8 = 30;
+const QUESTION_THICKNESS: f32 = 2.0;
+
+/// What the trainer teaches. Each one is a run of numbers along the wall, or the corner where
+/// the wall turns, that says where some of the tiles over it are mines or safe.
+#[derive(PartialEq, Clone, Copy)]
+pub enum Pattern {
+    /// A 1 beside a 1 in the corner, so the tile past the second is safe.
+    OneOne,
+    /// A 1 beside a 2, so the tile past the 2 is a mine.
+    OneTwo,
+    /// Mines beside the 1s and a safe tile beside the 2.
+    OneTwoOne,
+    /// Mines beside the 2s and safe tiles beside the 1s.
+    OneTwoTwoOne,
+    /// Three hidden tiles round the inside of a corner, read from the numbers either side.
+    Corner,
+    /// A 1-2-1 once the flags already beside it are taken off its numbers.
+    Reduced,
+}
+
+impl Pattern {
+    const ALL: [Pattern; 6] = [
+        Pattern::OneOne,
+        Pattern::OneTwo,
+        Pattern::OneTwoOne,
+        Pattern::OneTwoTwoOne,
+        Pattern::Corner,
+        Pattern::Reduced,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pattern::OneOne => "1-1",
+            Pattern::OneTwo => "1-2",
+            Pattern::OneTwoOne => "1-2-1",
+            Pattern::OneTwoTwoOne => "1-2-2-1",
+            Pattern::Corner => "Corner",
+            Pattern::Reduced => "Reduced 1-2-1",
+        }
+    }
+
+    /// The numbers along the wall, less any flags beside them.
+    fn numbers(self) -> &'static [u8] {
+        match self {
+            Pattern::OneOne => &[1, 1],
+            Pattern::OneTwo => &[1, 2],
+            Pattern::OneTwoOne | Pattern::Reduced => &[1, 2, 1],
+            Pattern::OneTwoTwoOne => &[1, 2, 2, 1],
+            Pattern::Corner => &[],
+        }
+    }
+}
+
+/// A tile the player is asked about, and whether it is a mine.
+type Question = ((usize, usize), bool);
+
+struct Position {
+    pattern: Pattern,
+    questions: Vec<Question>,
+    /// Whether the player got it right, once they have answered.
+    right: Option<bool>,
+}
+
+/// Deals small positions built round one pattern at a time. The player flags the mines and
+/// opens the safe tiles among those outlined, and a wrong move ends the position. Patterns the
+/// player gets wrong more often come up more often.
+pub struct Trainer {
+    rng: fastrand::Rng,
+    position: Option<Position>,
+}
+
+impl Trainer {
+    pub fn new() -> Self {
+        Self {
+            rng: fastrand::Rng::new(),
+            position: None,
+        }
+    }
+
+    /// Deals a position when there isn't one or the last one was answered and clicked away,
+    /// takes the player's answers and draws the board with the questions outlined.
+    pub fn frame(
+        &mut self,
+        board: &mut Board,
+        layout: &Layout,
+        settings: &Settings,
+        stats: &mut Stats,
+        theme: &Theme,
+    ) {
+        // the player answers every question, whoever the settings say makes the moves in games
+        let settings = &Settings {
+            mode: Mode::Human,
+            ..settings.clone()
+        };
+
+        let next = self.position.as_ref().is_none_or(|position| {
+            position.right.is_some()
+                && (is_mouse_button_pressed(MouseButton::Left) || is_key_pressed(KeyCode::Space))
+        });
+
+        // the layout was made for the old board, so drawing waits for the next frame
+        if next {
+            let pattern = self.choose(stats);
+            let (dealt, questions) = deal(pattern, &mut self.rng);
+            *board = dealt;
+            self.position = Some(Position {
+                pattern,
+                questions,
+                right: None,
+            });
+            return;
+        }
+
+        let Some(position) = &mut self.position else {
+            return;
+        };
+        let (mouse_x, mouse_y) = mouse_position();
+
+        if position.right.is_none() {
+            let asked = |tile| position.questions.iter().any(|&(asked, _)| asked == tile);
+
+            if let Some(action @ (Action::Reveal(col, row) | Action::Flag(col, row))) =
+                board.input_action(layout, settings, mouse_x, mouse_y)
+            {
+                if asked((col, row)) {
+                    board.apply(action, settings);
+                }
+            }
+
+            board.settle();
+
+            let answered = position.questions.iter().all(|&((col, row), mine)| {
+                if mine {
+                    board.flagged(col, row)
+                } else {
+                    board.revealed(col, row)
+                }
+            });
+
+            if board.state == State::Dead || answered {
+                let right = board.state != State::Dead;
+                position.right = Some(right);
+
+                // stops the clock and puts the sunglasses on
+                if right {
+                    board.state = State::Won;
+                }
+
+                stats.record_pattern(position.pattern.name(), right);
+                if let Err(error) = stats.save() {
+                    eprintln!("failed to save stats: {error:?}");
+                }
+            }
+        }
+
+        draw_frame(layout, &theme.colours);
+        draw_counters(board, layout, &theme.textures);
+        draw_smiley(smiley_for(board.state, &theme.textures), layout);
+
+        board.fade(get_frame_time());
+        draw_tiles(board, layout, theme, settings);
+
+        if position.right.is_none() {
+            for &((col, row), _) in &position.questions {
+                if !board.revealed(col, row) && !board.flagged(col, row) {
+                    let thickness = QUESTION_THICKNESS * layout.scale;
+                    let colour = settings.palette.flag_colour();
+                    outline_tile_shape(layout, col, row, thickness, colour);
+                }
+            }
+        }
+
+        let pattern_stats = stats.pattern(position.pattern.name());
+        let status = match position.right {
+            Some(true) => "Right! Click for more".to_owned(),
+            Some(false) => "Wrong. Click for more".to_owned(),
+            None => format!(
+                "{}: {}/{} right",
+                position.pattern.name(),
+                pattern_stats.right,
+                pattern_stats.answered
+            ),
+        };
+        let accuracy = pattern_stats.right as f32 / pattern_stats.answered.max(1) as f32;
+        let bar = settings.palette.flag_colour();
+        draw_status(&status, accuracy, bar, layout, &theme.colours);
+    }
+
+    /// Picks a pattern at random, weighted by how often the player has got it wrong. Every
+    /// pattern counts one wrong and one right answer more than it has, so patterns that
+    /// haven't come up yet still do.
+    fn choose(&mut self, stats: &Stats) -> Pattern {
+        let weights = Pattern::ALL.map(|pattern| {
+            let stats = stats.pattern(pattern.name());
+            (stats.answered - stats.right + 1) as f32 / (stats.answered + 2) as f32
+        });
+
+        let mut pick = self.rng.f32() * weights.iter().sum::<f32>();
+        for (pattern, weight) in Pattern::ALL.into_iter().zip(weights) {
+            if pick < weight {
+                return pattern;
+            }
+            pick -= weight;
+        }
+
+        Pattern::ALL[Pattern::ALL.len() - 1]
+    }
+}
+
+/// Scatters mines over the strip until the pattern turns up with something to ask about,
+/// returning the board and the questions.
+fn deal(pattern: Pattern, rng: &mut fastrand::Rng) -> (Board, Vec<Question>) {
+    loop {
+        let corner = pattern == Pattern::Corner;
+        let mut layout = Grid::new(WIDTH, HEIGHT, 0);
+        let mut open = Grid::new(WIDTH, HEIGHT, false);
+
+        for (col, row) in layout.positions() {
+            if row == WALL || corner && col == WIDTH - 1 {
+                open[(col, row)] = true;
+            } else if rng.u8(..100) < MINE_CHANCE {
+                layout[(col, row)] = 1;
+            }
+        }
+
+        let mut board = layout_board(&layout, Some(&open), Variant::default());
+        let Some(asked) = place(pattern, &mut board, rng) else {
+            continue;
+        };
+
+        let questions = deductions(&board)
+            .into_iter()
+            .filter(|(tile, _)| asked.contains(tile))
+            .collect::<Vec<_>>();
+
+        // the inside of the corner has to be one of the questions
+        let about_corner = !corner
+            || questions
+                .iter()
+                .any(|&(tile, _)| tile == (WIDTH - 2, WALL - 1));
+
+        if !questions.is_empty() && about_corner {
+            return (board, questions);
+        }
+    }
+}
+
+/// Finds the pattern on the board, flagging the mines beside it for a reduced pattern, and
+/// returns the hidden tiles round it that questions can be asked about.
+fn place(
+    pattern: Pattern,
+    board: &mut Board,
+    rng: &mut fastrand::Rng,
+) -> Option<Vec<(usize, usize)>> {
+    let hidden = |board: &Board, tiles: &[(usize, usize)]| {
+        tiles.iter().all(|&(col, row)| !board.revealed(col, row))
+    };
+
+    if pattern == Pattern::Corner {
+        let tiles = vec![
+            (WIDTH - 3, WALL - 1),
+            (WIDTH - 2, WALL - 1),
+            (WIDTH - 2, WALL - 2),
+        ];
+        return hidden(board, &tiles).then_some(tiles);
+    }
+
+    let numbers = pattern.numbers();
+    let starts = match pattern {
+        Pattern::OneOne => 0..1,
+        _ => 0..WIDTH - numbers.len() + 1,
+    };
+
+    let found = starts
+        .filter_map(|start| {
+            let end = start + numbers.len();
+            let tiles = (start.saturating_sub(1)..(end + 1).min(WIDTH))
+                .map(|col| (col, WALL - 1))
+                .collect::<Vec<_>>();
+
+            // the mines at either end come flagged in a reduced pattern
+            let flags = if pattern == Pattern::Reduced {
+                [start.checked_sub(1), Some(end).filter(|&end| end < WIDTH)]
+                    .into_iter()
+                    .flatten()
+                    .map(|col| (col, WALL - 1))
+                    .filter(|&(col, row)| board.mine(col, row))
+                    .collect::<Vec<_>>()
+            } else {
+                vec![]
+            };
+
+            let matches = (start..end).zip(numbers).all(|(col, &number)| {
+                let flagged = board
+                    .surrounding_tiles(col, WALL)
+                    .filter(|tile| flags.contains(tile))
+                    .count() as u8;
+                board.clue(col, WALL).checked_sub(flagged) == Some(number)
+            });
+
+            let reduced = pattern != Pattern::Reduced || !flags.is_empty();
+            (matches && reduced && hidden(board, &tiles)).then_some((tiles, flags))
+        })
+        .collect::<Vec<_>>();
+
+    if found.is_empty() {
+        return None;
+    }
+
+    let (tiles, flags) = found[rng.usize(..found.len())].clone();
+    for &(col, row) in &flags {
+        board.set_flagged(col, row, 1);
+    }
+
+    Some(
+        tiles
+            .into_iter()
+            .filter(|tile| !flags.contains(tile))
+            .collect(),
+    )
+}
+
+/// The hidden tiles next to the numbers that every way of placing mines to fit the numbers
+/// agrees on, and whether they are mines. Trying every way works on positions this small.
+fn deductions(board: &Board) -> Vec<Question> {
+    let numbers = board
+        .tiles
+        .positions()
+        .filter(|&(col, row)| board.revealed(col, row) && !board.mine(col, row))
+        .collect::<Vec<_>>();
+    let frontier = board
+        .tiles
+        .positions()
+        .filter(|&(col, row)| !board.revealed(col, row) && !board.flagged(col, row))
+        .filter(|&(col, row)| {
+            numbers.iter().any(|&(number_col, number_row)| {
+                board
+                    .surrounding_tiles(number_col, number_row)
+                    .any(|tile| tile == (col, row))
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // each number as the frontier tiles around it and the mines it has left to find
+    let constraints = numbers
+        .iter()
+        .map(|&(col, row)| {
+            let around = board
+                .surrounding_tiles(col, row)
+                .filter_map(|tile| frontier.iter().position(|&other| other == tile))
+                .fold(0u32, |mask, index| mask | 1 << index);
+            (around, board.effective_neighbour_mines(col, row) as u32)
+        })
+        .collect::<Vec<_>>();
+
+    let (mut always, mut ever) = (u32::MAX, 0);
+    for mines in 0..1u32 << frontier.len() {
+        if constraints
+            .iter()
+            .all(|&(around, left)| (mines & around).count_ones() == left)
+        {
+            always &= mines;
+            ever |= mines;
+        }
+    }
+
+    frontier
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, tile)| {
+            let bit = 1 << index;
+            if always & bit != 0 {
+                Some((tile, true))
+            } else if ever & bit == 0 {
+                Some((tile, false))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,375 @@
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use crossterm::{
+    cursor,
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute, queue,
+    style::{self, Attribute, Color, Stylize},
+    terminal::{self, ClearType},
+};
+use eyre::{Result, WrapErr};
+
+use crate::{
+    board_layout, finish_game,
+    grid::{Clues, Topology},
+    replay::Playback,
+    settings::{ChordStyle, Mode, Settings},
+    stats::Stats,
+    Action, Board, State, TileState,
+};
+
+// the header takes the first two lines, and every tile is two columns wide so the board comes
+// out roughly square
+const BOARD_TOP: u16 = 2;
+const BOARD_LEFT: u16 = 1;
+const TILE_WIDTH: u16 = 2;
+
+// how long to wait for input before drawing the clock again
+const TICK: Duration = Duration::from_millis(100);
+
+const HIDDEN: Color = Color::DarkGrey;
+
+const HELP: &str = "arrows/hjkl move  space reveal  f flag  n new game  q quit";
+
+/// Plays in the terminal instead of a window, with the same board and rules. Tiles are picked
+/// with the arrow keys or the mouse where the terminal reports it. Hex boards shift odd rows
+/// half a tile right as the window does, and triangles are laid out as a plain grid.
+pub fn run(settings: Settings, board: Board, playback: Option<Playback>) -> Result<()> {
+    let mut terminal = Terminal::enter().wrap_err("setting up the terminal")?;
+    let mut game = Game {
+        settings,
+        board,
+        playback,
+        stats: Stats::load(),
+        cursor: (0, 0),
+        notes: vec![],
+    };
+
+    game.cursor = (game.board.width() / 2, game.board.height() / 2);
+
+    loop {
+        game.step();
+        game.draw(&mut terminal.out)?;
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let quit = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => game.key(key),
+            Event::Mouse(mouse) => {
+                game.mouse(mouse);
+                false
+            }
+            Event::Resize(..) => {
+                queue!(terminal.out, terminal::Clear(ClearType::All))?;
+                false
+            }
+            _ => false,
+        };
+
+        if quit {
+            return Ok(());
+        }
+    }
+}
+
+/// Raw mode on the alternate screen for as long as it is alive, so the terminal is put back
+/// however the game ends.
+struct Terminal {
+    out: io::Stdout,
+}
+
+impl Terminal {
+    fn enter() -> Result<Self> {
+        let mut out = io::stdout();
+
+        terminal::enable_raw_mode()?;
+        execute!(
+            out,
+            terminal::EnterAlternateScreen,
+            EnableMouseCapture,
+            cursor::Hide,
+            terminal::Clear(ClearType::All)
+        )?;
+
+        Ok(Self { out })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(
+            self.out,
+            cursor::Show,
+            DisableMouseCapture,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct Game {
+    settings: Settings,
+    board: Board,
+    playback: Option<Playback>,
+    stats: Stats,
+    cursor: (usize, usize),
+    /// What the end of the last game had to say, shown under the board.
+    notes: Vec<String>,
+}
+
+impl Game {
+    /// Moves the game on by a tick: the replay or the bot, then the clock and the fog.
+    fn step(&mut self) {
+        let previous_state = self.board.state;
+
+        if let Some(playback) = &mut self.playback {
+            for action in playback.due() {
+                self.board.apply(action, &self.settings);
+            }
+        } else if self.settings.mode == Mode::Bot && self.board.state == State::Playing {
+            self.look();
+            self.board.computer_game(self.settings.solver);
+        }
+
+        self.finish(previous_state);
+
+        // nothing is animated here, so the opened tiles are never drawn away
+        self.board.opening.clear();
+        self.look();
+        self.board.fade(f32::INFINITY);
+    }
+
+    /// Works out the fog with the cursor standing in for the mouse.
+    fn look(&mut self) {
+        let layout = board_layout(&self.board, &self.settings);
+        let (x, y) = layout.tile_centre(self.cursor.0, self.cursor.1);
+        self.board.look(self.settings.fog, &layout, x, y);
+    }
+
+    /// Makes a move for the player. Replays and the bot leave the player only the first click.
+    fn apply(&mut self, action: Action) {
+        let bot = self.settings.mode == Mode::Bot && self.board.state != State::NewGame;
+        if self.playback.is_some() || bot {
+            return;
+        }
+
+        let previous_state = self.board.state;
+        self.board.apply(action, &self.settings);
+        self.finish(previous_state);
+    }
+
+    fn finish(&mut self, previous_state: State) {
+        self.board.settle();
+
+        let finished = matches!(previous_state, State::Playing | State::NewGame)
+            && matches!(self.board.state, State::Won | State::Dead)
+            && self.playback.is_none();
+
+        if finished {
+            self.notes = finish_game(&self.board, &self.settings, &mut self.stats);
+        }
+    }
+
+    /// The move a click or a press of space makes on a tile.
+    fn click(&mut self, (col, row): (usize, usize)) {
+        if self.board.state == State::NewGame || !self.board.revealed(col, row) {
+            if !self.board.flagged(col, row) {
+                self.apply(Action::Reveal(col, row));
+            }
+        } else if self.settings.chord_style != ChordStyle::Disabled
+            && self.board.satisfied(col, row)
+        {
+            self.apply(Action::Chord(col, row));
+        }
+    }
+
+    fn flag(&mut self, (col, row): (usize, usize)) {
+        if !self.board.revealed(col, row) {
+            self.apply(Action::Flag(col, row));
+        }
+    }
+
+    /// Handles a key press, returning whether it asked to quit.
+    fn key(&mut self, key: KeyEvent) -> bool {
+        let (col, row) = self.cursor;
+        let (width, height) = (self.board.width(), self.board.height());
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return true,
+            KeyCode::Left | KeyCode::Char('h') => self.cursor.0 = (col + width - 1) % width,
+            KeyCode::Right | KeyCode::Char('l') => self.cursor.0 = (col + 1) % width,
+            KeyCode::Up | KeyCode::Char('k') => self.cursor.1 = (row + height - 1) % height,
+            KeyCode::Down | KeyCode::Char('j') => self.cursor.1 = (row + 1) % height,
+            KeyCode::Char(' ') | KeyCode::Enter => self.click(self.cursor),
+            KeyCode::Char('f') => self.flag(self.cursor),
+            KeyCode::Char('s') if self.settings.mode == Mode::Assisted => {
+                self.look();
+                self.apply(Action::Solve);
+            }
+            KeyCode::Char('n') => {
+                self.board = self.board.restart();
+                self.playback = None;
+                self.notes.clear();
+            }
+            _ => {}
+        }
+
+        false
+    }
+
+    fn mouse(&mut self, mouse: MouseEvent) {
+        let Some(tile) = self.tile_at(mouse.column, mouse.row) else {
+            return;
+        };
+
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.cursor = tile;
+                self.click(tile);
+            }
+            MouseEventKind::Down(MouseButton::Middle) => {
+                self.cursor = tile;
+                if self.board.revealed(tile.0, tile.1) && self.board.satisfied(tile.0, tile.1) {
+                    self.apply(Action::Chord(tile.0, tile.1));
+                }
+            }
+            MouseEventKind::Down(MouseButton::Right) => {
+                self.cursor = tile;
+                self.flag(tile);
+            }
+            MouseEventKind::Moved => self.cursor = tile,
+            _ => {}
+        }
+    }
+
+    /// Terminal column the tiles of a row start at.
+    fn row_start(&self, row: usize) -> u16 {
+        let shift = self.board.topology == Topology::Hex && row % 2 == 1;
+        BOARD_LEFT + if shift { TILE_WIDTH / 2 } else { 0 }
+    }
+
+    fn tile_at(&self, column: u16, line: u16) -> Option<(usize, usize)> {
+        let row = line.checked_sub(BOARD_TOP)? as usize;
+        let col = (column.checked_sub(self.row_start(row))? / TILE_WIDTH) as usize;
+
+        (col < self.board.width() && row < self.board.height()).then_some((col, row))
+    }
+
+    fn draw(&self, out: &mut impl Write) -> Result<()> {
+        let board = &self.board;
+        let face = match board.state {
+            State::Dead => "X(",
+            State::Won => "B)",
+            _ => ":)",
+        };
+        let board_width = board.width() as u16 * TILE_WIDTH + TILE_WIDTH / 2;
+
+        // the same counters as the window: mines left on the left, seconds on the right
+        queue!(
+            out,
+            cursor::MoveTo(BOARD_LEFT, 0),
+            terminal::Clear(ClearType::CurrentLine),
+            style::PrintStyledContent(
+                format!("{:03}", board.mines.saturating_sub(board.number_flagged)).red()
+            ),
+            cursor::MoveTo(BOARD_LEFT + board_width / 2 - 1, 0),
+            style::Print(face),
+            cursor::MoveTo(BOARD_LEFT + board_width - 3, 0),
+            style::PrintStyledContent(format!("{:03}", board.elapsed.min(999)).red()),
+        )?;
+
+        for row in 0..board.height() {
+            queue!(
+                out,
+                cursor::MoveTo(0, BOARD_TOP + row as u16),
+                terminal::Clear(ClearType::CurrentLine),
+                cursor::MoveTo(self.row_start(row), BOARD_TOP + row as u16),
+            )?;
+
+            for col in 0..board.width() {
+                let mut tile = self.tile(col, row);
+
+                if (col, row) == self.cursor && self.playback.is_none() {
+                    tile = tile.attribute(Attribute::Reverse);
+                }
+
+                queue!(out, style::PrintStyledContent(tile), style::Print(" "))?;
+            }
+        }
+
+        let lines = self.notes.iter().map(String::as_str).chain([HELP]);
+        for (line, text) in (BOARD_TOP + board.height() as u16 + 1..).zip(lines) {
+            queue!(
+                out,
+                cursor::MoveTo(BOARD_LEFT, line),
+                terminal::Clear(ClearType::CurrentLine),
+                style::Print(text),
+            )?;
+        }
+
+        out.flush()?;
+        Ok(())
+    }
+
+    /// What a tile shows, following the same rules as drawing it in the window.
+    fn tile(&self, col: usize, row: usize) -> style::StyledContent<char> {
+        let board = &self.board;
+        let tile = &board.tiles[(col, row)];
+        let dead = board.state == State::Dead;
+
+        if dead && board.mine(col, row) {
+            let mine = '*'.black().bold();
+
+            return if board.unflagged_mines.contains(&(col, row)) {
+                mine.on_red()
+            } else {
+                mine
+            };
+        }
+
+        if dead && board.flagged(col, row) {
+            return 'X'.red().bold();
+        }
+
+        match tile.state {
+            TileState::Hidden => '#'.with(HIDDEN),
+            TileState::Question => '?'.with(HIDDEN),
+            TileState::Flagged if tile.flags > 1 => char::from_digit(tile.flags as u32, 10)
+                .unwrap_or('F')
+                .red()
+                .bold(),
+            TileState::Flagged => 'F'.red().bold(),
+            TileState::Revealed => {
+                let clue = tile.clue;
+
+                // a lying 0 still has to be shown, only honest ones are left blank
+                if clue == 0 && board.clues != Clues::Liar {
+                    '.'.with(HIDDEN)
+                } else if board.visibility[(col, row)] < 1.0 {
+                    '~'.with(HIDDEN)
+                } else {
+                    // black numbers would vanish on a dark terminal, so they take its own colour
+                    let rgba: [u8; 4] = self.settings.palette.number_colour(clue).into();
+                    let colour = match rgba {
+                        [0, 0, 0, _] => Color::Reset,
+                        [r, g, b, _] => Color::Rgb { r, g, b },
+                    };
+
+                    char::from_digit(clue as u32, 10)
+                        .unwrap_or('+')
+                        .with(colour)
+                        .bold()
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,446 @@
+use eyre::{bail, ensure, Result, WrapErr};
+use macroquad::prelude::*;
+
+use crate::{
+    draw_bevel, draw_counters, draw_frame, draw_smiley, draw_tiles,
+    grid::Grid,
+    outline_tile_shape,
+    replay::{layout_board, parse_rows},
+    settings::{ChordStyle, Mode, Settings, Variant},
+    smiley_for,
+    theme::{Colours, Theme},
+    Action, Board, Layout, State, PANEL_MARGIN,
+};
+
+/// Height of the panel under the board that the lessons are written in, in unscaled pixels.
+pub const PANEL_HEIGHT: f32 = 112.0;
+const PANEL_BEVEL: f32 = 2.0;
+const FONT_SIZE: f32 = 14.0;
+const LINE_HEIGHT: f32 = 16.0;
+const OUTLINE_THICKNESS: f32 = 2.0;
+
+/// The lessons, played in order. Each one has a board in the same rows as board files, then
+/// its steps: `text` lines saying what to do, `show` lines outlining tiles to look at, and a
+/// `do` line with the move that finishes the step.
+const LESSONS: &str = include_str!("../assets/tutorial.txt");
+
+struct Step {
+    text: String,
+    shown: Vec<(usize, usize)>,
+    action: Action,
+}
+
+struct Lesson {
+    title: String,
+    layout: Grid<u8>,
+    given: Grid<bool>,
+    steps: Vec<Step>,
+}
+
+impl Lesson {
+    fn board(&self) -> Board {
+        let given = self.given.iter().any(|&given| given).then_some(&self.given);
+        layout_board(&self.layout, given, Variant::default())
+    }
+}
+
+/// Walks a new player through the lessons on boards laid out by hand. Only the move a step
+/// asks for is made, anything else is left undone with a hint to try again.
+pub struct Tutorial {
+    lessons: Vec<Lesson>,
+    lesson: usize,
+    step: usize,
+    /// Whether the last move tried wasn't the one asked for.
+    missed: bool,
+}
+
+impl Tutorial {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            lessons: parse(LESSONS).wrap_err("reading the tutorial")?,
+            lesson: 0,
+            step: 0,
+            missed: false,
+        })
+    }
+
+    /// The board for the lesson being played.
+    pub fn board(&self) -> Board {
+        self.lessons[self.lesson].board()
+    }
+
+    /// Takes the player's move if it is the one the step asks for, and draws the board with
+    /// the step's tiles outlined over the panel saying what to do.
+    pub fn frame(
+        &mut self,
+        board: &mut Board,
+        layout: &Layout,
+        settings: &Settings,
+        theme: &Theme,
+    ) {
+        let steps = self.lessons[self.lesson].steps.len();
+        let last_lesson = self.lesson + 1 == self.lessons.len();
+
+        // the layout was made for the old board, so drawing waits for the next frame
+        if self.step == steps
+            && !last_lesson
+            && (is_mouse_button_pressed(MouseButton::Left) || is_key_pressed(KeyCode::Space))
+        {
+            self.lesson += 1;
+            self.step = 0;
+            *board = self.board();
+            return;
+        }
+
+        // the player makes every move, and chording has a lesson of its own so it is on
+        // whatever the settings say
+        let settings = &Settings {
+            mode: Mode::Human,
+            chord_style: match settings.chord_style {
+                ChordStyle::Disabled => ChordStyle::Classic,
+                chord_style => chord_style,
+            },
+            ..settings.clone()
+        };
+
+        if self.step < steps {
+            let (mouse_x, mouse_y) = mouse_position();
+            let asked = self.lessons[self.lesson].steps[self.step].action;
+
+            match board.input_action(layout, settings, mouse_x, mouse_y) {
+                Some(action) if action == asked => {
+                    board.apply(action, settings);
+                    self.step += 1;
+                    self.missed = false;
+
+                    // stops the clock and puts the sunglasses on
+                    if self.step == steps {
+                        board.state = State::Won;
+                    }
+                }
+                // clicking on an open tile is half of a chord rather than a move
+                Some(Action::Reveal(col, row)) if board.revealed(col, row) => {}
+                Some(_) => self.missed = true,
+                None => {}
+            }
+
+            board.settle();
+        }
+
+        draw_frame(layout, &theme.colours);
+        draw_counters(board, layout, &theme.textures);
+        draw_smiley(smiley_for(board.state, &theme.textures), layout);
+
+        board.fade(get_frame_time());
+        draw_tiles(board, layout, theme, settings);
+
+        let lesson = &self.lessons[self.lesson];
+        let text = match lesson.steps.get(self.step) {
+            Some(step) => {
+                let thickness = OUTLINE_THICKNESS * layout.scale;
+                for &(col, row) in &step.shown {
+                    outline_tile_shape(layout, col, row, thickness, settings.palette.flag_colour());
+                }
+
+                if self.missed {
+                    format!("Not quite. {}", step.text)
+                } else {
+                    step.text.clone()
+                }
+            }
+            None if last_lesson => {
+                "That's everything. Press escape for the menu and start a game.".to_owned()
+            }
+            None => "Well done! Click for the next lesson.".to_owned(),
+        };
+
+        let title = format!(
+            "Lesson {}/{}: {}",
+            self.lesson + 1,
+            self.lessons.len(),
+            lesson.title
+        );
+        draw_panel(&title, &text, layout, &theme.colours);
+    }
+}
+
+/// Draws the panel under the board with the lesson's title over `text`, wrapped to fit.
+fn draw_panel(title: &str, text: &str, layout: &Layout, colours: &Colours) {
+    let scale = layout.scale;
+    let top = layout.window_height;
+
+    draw_rectangle(
+        layout.origin_x,
+        top,
+        layout.window_width,
+        PANEL_HEIGHT * scale,
+        colours.face,
+    );
+    draw_bevel(
+        layout.origin_x,
+        top,
+        layout.window_width,
+        PANEL_HEIGHT * scale,
+        PANEL_BEVEL * scale,
+        colours.highlight,
+        colours.shadow,
+    );
+
+    let font_size = (FONT_SIZE * scale) as u16;
+    let x = layout.origin_x + PANEL_MARGIN * scale;
+    let lines = std::iter::once(title.to_owned()).chain(wrap(text, layout.panel_width, font_size));
+
+    for (line, number) in lines.zip(1..) {
+        let y = top + PANEL_MARGIN * scale + number as f32 * LINE_HEIGHT * scale;
+        draw_text(&line, x, y, font_size as f32, colours.text);
+    }
+}
+
+/// Breaks `text` into lines no wider than `width` between words.
+fn wrap(text: &str, width: f32, font_size: u16) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line)
+                if measure_text(&format!("{line} {word}"), None, font_size, 1.0).width <= width =>
+            {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_owned()),
+        }
+    }
+
+    lines
+}
+
+/// A lesson as far as it has been read.
+struct Draft<'a> {
+    title: String,
+    rows: Vec<&'a str>,
+    steps: Vec<Step>,
+    text: Vec<&'a str>,
+    shown: Vec<(usize, usize)>,
+}
+
+fn parse(s: &str) -> Result<Vec<Lesson>> {
+    let mut drafts: Vec<Draft> = vec![];
+
+    for (number, line) in s.lines().enumerate() {
+        let line = line.trim();
+        let context = || format!("line {}", number + 1);
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        if keyword == "lesson" {
+            drafts.push(Draft {
+                title: rest.to_owned(),
+                rows: vec![],
+                steps: vec![],
+                text: vec![],
+                shown: vec![],
+            });
+            continue;
+        }
+
+        let Some(draft) = drafts.last_mut() else {
+            bail!("{}: expected a lesson line first", context());
+        };
+
+        match keyword {
+            "row" => draft.rows.push(rest),
+            "text" => draft.text.push(rest),
+            "show" => {
+                let tile = rest
+                    .split_whitespace()
+                    .map(str::parse)
+                    .collect::<Result<Vec<usize>, _>>()
+                    .wrap_err_with(context)?;
+                let [col, row] = tile[..] else {
+                    bail!("{}: expected a column and row", context());
+                };
+                draft.shown.push((col, row));
+            }
+            "do" => {
+                ensure!(
+                    !draft.text.is_empty(),
+                    "{}: the step has no text",
+                    context()
+                );
+                draft.steps.push(Step {
+                    text: draft.text.join(" "),
+                    shown: std::mem::take(&mut draft.shown),
+                    action: rest.parse().wrap_err_with(context)?,
+                });
+                draft.text.clear();
+            }
+            _ => bail!("{}: unexpected {line:?}", context()),
+        }
+    }
+
+    ensure!(!drafts.is_empty(), "there are no lessons");
+    drafts.into_iter().map(check).collect()
+}
+
+/// Puts a lesson's board together and plays its steps through, so that a lesson that can't be
+/// finished never gets as far as a player.
+fn check(draft: Draft) -> Result<Lesson> {
+    let title = draft.title;
+    ensure!(
+        draft.text.is_empty() && draft.shown.is_empty(),
+        "{title:?} has a step without a do line"
+    );
+    ensure!(!draft.steps.is_empty(), "{title:?} has no steps");
+
+    let (layout, given) = parse_rows(&draft.rows).wrap_err_with(|| format!("lesson {title:?}"))?;
+    let lesson = Lesson {
+        title,
+        layout,
+        given,
+        steps: draft.steps,
+    };
+
+    let settings = Settings::default();
+    let mut board = lesson.board();
+
+    for (number, step) in lesson.steps.iter().enumerate() {
+        let acted = match step.action {
+            Action::Reveal(col, row) | Action::Flag(col, row) | Action::Chord(col, row) => {
+                Some((col, row))
+            }
+            Action::Solve => None,
+        };
+        let on_board = step
+            .shown
+            .iter()
+            .chain(&acted)
+            .all(|&(col, row)| col < board.width() && row < board.height());
+        ensure!(
+            on_board,
+            "step {} of {:?} points at a tile off the board",
+            number + 1,
+            lesson.title
+        );
+
+        if let Action::Flag(col, row) = step.action {
+            ensure!(
+                board.mine(col, row),
+                "step {} of {:?} flags a safe tile",
+                number + 1,
+                lesson.title
+            );
+        }
+
+        let before = (board.revealed_safe, board.number_flagged);
+        board.apply(step.action, &settings);
+        board.settle();
+        ensure!(
+            (board.revealed_safe, board.number_flagged) != before && board.state != State::Dead,
+            "step {} of {:?} asks for {}, which can't be played",
+            number + 1,
+            lesson.title,
+            step.action
+        );
+    }
+
+    Ok(lesson)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lessons_are_read_step_by_step() {
+        let lessons = parse(
+            "# one lesson
+            lesson Flags
+            row *-.*
+            row --..
+            text Flag the mine
+            text in the corner.
+            show 0 0
+            do flag 0 0
+            text Now reveal the rest.
+            show 2 1
+            show 2 0
+            do reveal 2 1",
+        )
+        .unwrap();
+
+        let [lesson] = &lessons[..] else {
+            panic!("expected one lesson");
+        };
+        assert_eq!(lesson.title, "Flags");
+        assert_eq!((lesson.layout.width(), lesson.layout.height()), (4, 2));
+
+        let [flag, reveal] = &lesson.steps[..] else {
+            panic!("expected two steps");
+        };
+        assert_eq!(flag.text, "Flag the mine in the corner.");
+        assert_eq!(flag.shown, [(0, 0)]);
+        assert_eq!(flag.action, Action::Flag(0, 0));
+        assert_eq!(reveal.shown, [(2, 1), (2, 0)]);
+        assert_eq!(reveal.action, Action::Reveal(2, 1));
+    }
+
+    #[test]
+    fn lessons_that_cannot_be_played_are_refused() {
+        for (text, error) in [
+            ("", "there are no lessons"),
+            ("row *..", "expected a lesson line first"),
+            ("lesson A\nrow *..", "has no steps"),
+            ("lesson A\nrow *..\ndo flag 0 0", "the step has no text"),
+            ("lesson A\nrow *..\ntext Go.\ndo dance", "line 4"),
+            (
+                "lesson A\nrow *..\ntext Go.\nshow 1\ndo flag 0 0",
+                "a column and row",
+            ),
+            (
+                "lesson A\nrow *..\ntext Go.\ndo flag 0 0\ntext Then?",
+                "without a do line",
+            ),
+            (
+                "lesson A\nrow *..\ntext Go.\ndo reveal 0 0",
+                "can't be played",
+            ),
+            (
+                "lesson A\nrow *-.\ntext Go.\ndo reveal 1 0",
+                "can't be played",
+            ),
+            (
+                "lesson A\nrow *..\ntext Go.\ndo flag 1 0",
+                "flags a safe tile",
+            ),
+            (
+                "lesson A\nrow *..\ntext Go.\ndo reveal 3 0",
+                "off the board",
+            ),
+            (
+                "lesson A\nrow *..\ntext Go.\nshow 0 1\ndo flag 0 0",
+                "off the board",
+            ),
+            (
+                "lesson A\nrow *.\nrow ...\ntext Go.\ndo flag 0 0",
+                "lesson \"A\"",
+            ),
+        ] {
+            let report = parse(text)
+                .err()
+                .unwrap_or_else(|| panic!("{text:?} parsed"));
+            assert!(report.to_string().contains(error), "{text:?}: {report}");
+        }
+    }
+
+    #[test]
+    fn the_bundled_lessons_can_all_be_played() {
+        let tutorial = Tutorial::new().unwrap();
+        assert_eq!(tutorial.lessons.len(), 4);
+    }
+}
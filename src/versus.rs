@@ -0,0 +1,504 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+use eyre::{bail, eyre, Result, WrapErr};
+use macroquad::prelude::*;
+
+use crate::{
+    draw_bevel,
+    race::draw_banner,
+    replay::Replay,
+    settings::{Difficulty, Settings},
+    theme::{Colours, Theme},
+    Action, Board, Layout, State, PANEL_MARGIN,
+};
+
+/// Height of the strip under the board showing how the opponent is doing, unscaled.
+pub const STATUS_HEIGHT: f32 = 24.0;
+const STATUS_BEVEL: f32 = 2.0;
+const STATUS_FONT_SIZE: f32 = 14.0;
+
+// how long to wait before trying to connect again
+const RETRY: Duration = Duration::from_secs(1);
+
+/// What the network thread has to tell the game.
+enum Event {
+    /// A peer connected, with a handle for writing to it.
+    Connected(TcpStream),
+    Line(String),
+    Disconnected,
+}
+
+/// A race against another player on another machine, each on their own copy of the same board.
+///
+/// The two games talk in lines of text. A game is sent as its replay with the lines joined by
+/// `;`, and every move after that as the line the replay would have for it. The replay names
+/// the settings that change what moves do, so each side makes the other's moves by the other's
+/// settings.
+///
+/// ```text
+/// game size 9 9 10;seed 1234;0.000 reveal 4 4
+/// 1.250 flag 5 6
+/// new
+/// ```
+///
+/// The host decides which board is played. Whenever someone connects or the host starts a new
+/// game it sends its game, and the player who joined takes that board and answers with their
+/// own game so far. The player who joined asks for a new game with `new`. Whoever clicks first
+/// opens both boards, and if both click before hearing about the other, the host's click
+/// stands. Dropped connections are picked up again, with both sides sending their game anew.
+pub struct Versus {
+    host: bool,
+    /// Where the other player is, as shown while waiting for them.
+    address: String,
+    events: Receiver<Event>,
+    peer: Option<TcpStream>,
+    /// Whether a peer was ever connected, so that waiting for them again reads as reconnecting.
+    connected_before: bool,
+    /// The opponent's game as far as their moves have come in.
+    opponent: Option<Board>,
+    /// The opponent's settings, which their moves are made by.
+    opponent_settings: Settings,
+    /// Seconds the opponent took to clear their board, once they have.
+    opponent_seconds: Option<f32>,
+    /// The seed of the game being sent, and how many of its moves have been.
+    seed: u64,
+    sent: usize,
+}
+
+impl Versus {
+    /// Waits for the other player on `port`, on every network the machine is on.
+    pub fn host(port: u16, board: &Board) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))
+            .wrap_err_with(|| format!("listening on port {port}"))?;
+        let address = format!("port {}", listener.local_addr()?.port());
+        let (events, receiver) = mpsc::channel();
+
+        println!("waiting for a player on {address}");
+        thread::spawn(move || {
+            while let Ok((stream, _)) = listener.accept() {
+                if !talk(stream, &events) {
+                    return;
+                }
+            }
+        });
+
+        Ok(Self::new(true, address, receiver, board))
+    }
+
+    /// Connects to a player hosting at `address`, such as `192.168.1.20:4000`, trying again
+    /// until they are there.
+    pub fn join(address: String, board: &Board) -> Self {
+        let (events, receiver) = mpsc::channel();
+
+        let connecting_to = address.clone();
+        thread::spawn(move || loop {
+            match TcpStream::connect(&connecting_to) {
+                Ok(stream) => {
+                    if !talk(stream, &events) {
+                        return;
+                    }
+                }
+                Err(_) => thread::sleep(RETRY),
+            }
+        });
+
+        Self::new(false, address, receiver, board)
+    }
+
+    fn new(host: bool, address: String, events: Receiver<Event>, board: &Board) -> Self {
+        Self {
+            host,
+            address,
+            events,
+            peer: None,
+            connected_before: false,
+            opponent: None,
+            opponent_settings: Settings::default(),
+            opponent_seconds: None,
+            seed: board.seed,
+            sent: 0,
+        }
+    }
+
+    /// Swaps moves with the other player and draws how they are doing under the board.
+    pub fn frame(
+        &mut self,
+        board: &mut Board,
+        layout: &Layout,
+        settings: &mut Settings,
+        theme: &Theme,
+    ) {
+        self.exchange(board, settings);
+        let (status, progress) = self.status();
+        let bar = settings.palette.flag_colour();
+        draw_status(&status, progress, bar, layout, &theme.colours);
+
+        if let Some(result) = self.result(board) {
+            draw_banner(result, layout.window_width / 2.0, layout, &theme.colours);
+        }
+    }
+
+    /// Plays what has come in from the other player and sends them what has happened here.
+    fn exchange(&mut self, board: &mut Board, settings: &mut Settings) {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Event::Connected(peer) => {
+                    println!("playing against {}", self.address);
+                    self.peer = Some(peer);
+                    self.connected_before = true;
+                    self.opponent = None;
+                    self.opponent_seconds = None;
+
+                    if self.host {
+                        self.send_game(board, settings);
+                    }
+                }
+                Event::Disconnected => {
+                    println!("lost the connection to {}", self.address);
+                    self.peer = None;
+                }
+                Event::Line(line) => {
+                    if let Err(error) = self.receive(&line, board, settings) {
+                        eprintln!("ignoring {line:?} from the other player: {error}");
+                    }
+                }
+            }
+        }
+
+        // a new game started here, from the smiley or the menu
+        if board.seed != self.seed {
+            self.opponent = None;
+            self.opponent_seconds = None;
+
+            if self.host {
+                self.send_game(board, settings);
+            } else {
+                self.seed = board.seed;
+                self.sent = board.history.len();
+                self.send("new");
+            }
+        }
+
+        for index in self.sent..board.history.len() {
+            let (seconds, action) = board.history[index];
+            self.send(&format!("{seconds:.3} {action}"));
+        }
+        self.sent = board.history.len();
+    }
+
+    fn receive(&mut self, line: &str, board: &mut Board, settings: &mut Settings) -> Result<()> {
+        if let Some(game) = line.strip_prefix("game ") {
+            let replay = game.replace(';', "\n").parse::<Replay>()?;
+            let opponent = replay.replayed(settings);
+            self.opponent_settings = replay.settings(settings);
+
+            if !self.host {
+                if !same_board(&opponent, board) {
+                    *board = replay.board();
+                    settings.difficulty = difficulty_of(board);
+                }
+
+                self.send_game(board, settings);
+            }
+
+            // a game from before the host last started again is left for the one that follows
+            if same_board(&opponent, board) {
+                self.opponent_seconds = (opponent.state == State::Won).then(|| replay.seconds());
+                self.opponent = Some(opponent);
+                self.open_from_opponent(board, settings);
+            }
+
+            return Ok(());
+        }
+
+        if line == "new" {
+            if self.host {
+                *board = board.restart();
+            }
+
+            return Ok(());
+        }
+
+        let (seconds, action) = line
+            .split_once(' ')
+            .ok_or_else(|| eyre!("not a game, move or new"))?;
+        let seconds = seconds.parse::<f32>()?;
+        let action = action.parse::<Action>()?;
+
+        let Some(opponent) = &mut self.opponent else {
+            return Ok(());
+        };
+
+        if let Action::Reveal(col, row) | Action::Flag(col, row) | Action::Chord(col, row) = action
+        {
+            if col >= opponent.width() || row >= opponent.height() {
+                bail!("{action} is off the board");
+            }
+        }
+
+        opponent.apply(action, &self.opponent_settings);
+        opponent.settle();
+
+        if opponent.state == State::Won && self.opponent_seconds.is_none() {
+            self.opponent_seconds = Some(seconds);
+        }
+
+        self.open_from_opponent(board, settings);
+        Ok(())
+    }
+
+    /// Opens the board with the opponent's first click if it hasn't been opened here, or if
+    /// this side joined and opened it with a different click at the same time as the host.
+    fn open_from_opponent(&mut self, board: &mut Board, settings: &Settings) {
+        let Some(&(_, first @ Action::Reveal(..))) = self
+            .opponent
+            .as_ref()
+            .and_then(|opponent| opponent.history.first())
+        else {
+            return;
+        };
+
+        let own_first = board.history.first().map(|&(_, action)| action);
+        if own_first.is_some_and(|own_first| self.host || own_first == first) {
+            return;
+        }
+
+        let clashed = own_first.is_some();
+        if clashed {
+            *board = board.reseeded(board.seed);
+        }
+
+        board.apply(first, settings);
+
+        // the host has the other click, so needs the game again rather than the moves after it
+        if clashed {
+            self.send_game(board, settings);
+        }
+    }
+
+    fn send_game(&mut self, board: &Board, settings: &Settings) {
+        let game = Replay::new(board, settings).to_string();
+        self.send(&format!("game {}", game.trim_end().replace('\n', ";")));
+        self.seed = board.seed;
+        self.sent = board.history.len();
+    }
+
+    fn send(&mut self, line: &str) {
+        let Some(peer) = &mut self.peer else {
+            return;
+        };
+
+        // the network thread notices the connection going and tries again
+        if writeln!(peer, "{line}").is_err() {
+            self.peer = None;
+        }
+    }
+
+    /// What to announce once the race is over. Both sides clearing their boards is settled by
+    /// who took less time, with the host winning a tie.
+    fn result(&self, board: &Board) -> Option<&'static str> {
+        // to the millisecond, as the opponent hears it, so that both sides agree on a tie
+        let seconds = (board.state == State::Won).then(|| {
+            let seconds = board.history.last().map_or(0.0, |&(seconds, _)| seconds);
+            (seconds * 1000.0).round() / 1000.0
+        });
+        let opponent_state = self.opponent.as_ref().map(|opponent| opponent.state);
+
+        match (seconds, self.opponent_seconds) {
+            (Some(seconds), Some(opponent_seconds)) => {
+                if seconds < opponent_seconds || seconds == opponent_seconds && self.host {
+                    Some("You win")
+                } else {
+                    Some("Opponent wins")
+                }
+            }
+            (Some(_), None) => Some("You win"),
+            (None, Some(_)) => Some("Opponent wins"),
+            (None, None) if board.state == State::Dead && opponent_state == Some(State::Dead) => {
+                Some("Nobody wins")
+            }
+            (None, None) => None,
+        }
+    }
+
+    /// What the strip under the board says about the opponent, and how far along they are.
+    fn status(&self) -> (String, f32) {
+        match (&self.peer, &self.opponent) {
+            (None, _) if self.host && !self.connected_before => {
+                (format!("Waiting on {}", self.address), 0.0)
+            }
+            (None, _) if self.connected_before => ("Reconnecting...".to_owned(), 0.0),
+            (None, _) => ("Connecting...".to_owned(), 0.0),
+            (Some(_), None) => ("Opponent hasn't started".to_owned(), 0.0),
+            (Some(_), Some(opponent)) => {
+                let progress = opponent.revealed_safe as f32 / opponent.safe_tiles.max(1) as f32;
+
+                match opponent.state {
+                    State::Won => ("Opponent cleared it".to_owned(), 1.0),
+                    State::Dead => ("Opponent hit a mine".to_owned(), progress),
+                    _ => (format!("Opponent {:.0}%", 100.0 * progress), progress),
+                }
+            }
+        }
+    }
+}
+
+/// Draws the strip under the board for games over the network: `status` over a bar filled
+/// `progress` of the way in `bar`.
+pub fn draw_status(status: &str, progress: f32, bar: Color, layout: &Layout, colours: &Colours) {
+    let Colours {
+        face,
+        highlight,
+        shadow,
+        text,
+    } = *colours;
+    let scale = layout.scale;
+    let top = layout.window_height;
+    let height = STATUS_HEIGHT * scale;
+
+    draw_rectangle(layout.origin_x, top, layout.window_width, height, face);
+    draw_bevel(
+        layout.origin_x,
+        top,
+        layout.window_width,
+        height,
+        STATUS_BEVEL * scale,
+        highlight,
+        shadow,
+    );
+
+    let (well_x, well_y) = (layout.origin_x + PANEL_MARGIN * scale, top + 4.0 * scale);
+    let (well_width, well_height) = (layout.panel_width, height - 8.0 * scale);
+
+    draw_rectangle(well_x, well_y, well_width * progress, well_height, bar);
+    draw_bevel(
+        well_x,
+        well_y,
+        well_width,
+        well_height,
+        scale,
+        shadow,
+        highlight,
+    );
+
+    let font_size = (STATUS_FONT_SIZE * scale) as u16;
+    let dimensions = measure_text(status, None, font_size, 1.0);
+    draw_text(
+        status,
+        well_x + (well_width - dimensions.width) / 2.0,
+        well_y + (well_height + dimensions.offset_y) / 2.0,
+        font_size as f32,
+        text,
+    );
+}
+
+/// The difficulty a board of this size is recorded under in the stats.
+fn difficulty_of(board: &Board) -> Difficulty {
+    let size = (board.width(), board.height(), board.mines);
+
+    Difficulty::STANDARD
+        .into_iter()
+        .find(|difficulty| (difficulty.width(), difficulty.height(), difficulty.mines()) == size)
+        .unwrap_or(Difficulty::Custom {
+            width: size.0,
+            height: size.1,
+            mines: size.2,
+        })
+}
+
+/// Whether two boards are the same game, with the same mines waiting to be placed.
+fn same_board(a: &Board, b: &Board) -> bool {
+    a.seed == b.seed
+        && (a.width(), a.height(), a.mines) == (b.width(), b.height(), b.mines)
+        && a.variant() == b.variant()
+}
+
+/// Passes on what a peer sends until it goes, returning whether the game is still there to
+/// pass it to.
+fn talk(stream: TcpStream, events: &Sender<Event>) -> bool {
+    let _ = stream.set_nodelay(true);
+
+    let Ok(writer) = stream.try_clone() else {
+        return true;
+    };
+
+    if events.send(Event::Connected(writer)).is_err() {
+        return false;
+    }
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        if events.send(Event::Line(line)).is_err() {
+            return false;
+        }
+    }
+
+    events.send(Event::Disconnected).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{Difficulty, Variant};
+
+    #[test]
+    fn moves_off_the_board_are_refused() {
+        let mut board = crate::new_board(Difficulty::Beginner, Variant::default(), 1);
+        let mut settings = Settings::default();
+        let (_, receiver) = mpsc::channel();
+        let mut versus = Versus::new(false, String::new(), receiver, &board);
+        versus.opponent = Some(board.reseeded(board.seed));
+
+        for line in ["0.000 reveal 99 99", "0.000 flag 9 0", "0.000 chord 0 9"] {
+            assert!(versus.receive(line, &mut board, &mut settings).is_err());
+        }
+
+        versus
+            .receive("0.000 reveal 4 4", &mut board, &mut settings)
+            .unwrap();
+        assert!(versus.opponent.unwrap().revealed(4, 4));
+    }
+
+    #[test]
+    fn the_opponent_plays_by_their_own_settings() {
+        let mut board = crate::new_board(Difficulty::Beginner, Variant::default(), 1);
+        let mut settings = Settings::default();
+        let (_, receiver) = mpsc::channel();
+        let mut versus = Versus::new(false, String::new(), receiver, &board);
+
+        let theirs = Settings {
+            question_marks: true,
+            ..Settings::default()
+        };
+        let mut opponent = board.reseeded(board.seed);
+        opponent.apply(Action::Reveal(4, 4), &theirs);
+        let game = Replay::new(&opponent, &theirs)
+            .to_string()
+            .replace('\n', ";");
+        versus
+            .receive(&format!("game {game}"), &mut board, &mut settings)
+            .unwrap();
+
+        let (col, row) = opponent
+            .tiles
+            .positions()
+            .find(|&(col, row)| !opponent.revealed(col, row))
+            .unwrap();
+        for _ in 0..2 {
+            let line = format!("1.000 {}", Action::Flag(col, row));
+            versus.receive(&line, &mut board, &mut settings).unwrap();
+        }
+
+        let opponent = versus.opponent.unwrap();
+        assert!(opponent.tiles[(col, row)].state == crate::TileState::Question);
+    }
+}